@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use designtime_rs::lexer::TokenKind;
+use designtime_rs::{Lexer, Parser};
+
+/// A large generated page: many cards with attributes, text and the odd entity.
+fn generated_page(cards: usize) -> String {
+    let mut page = String::from("<main class=\"flex flex-col p-4\">\n");
+    for i in 0..cards {
+        page.push_str(&format!(
+            "  <section class=\"card p-2 bg-white\" data-id=\"{i}\">\n    <h2 class=\"font-bold\">Card {i}</h2>\n    <p>Some body text for card {i} &mdash; with a link: <a href=\"/cards/{i}\">read more</a></p>\n  </section>\n"
+        ));
+    }
+    page.push_str("</main>\n");
+    page
+}
+
+fn lex_all(source: &str) -> usize {
+    let mut lexer = Lexer::new(source);
+    let mut count = 0;
+    while lexer.next_token().kind != TokenKind::EOF {
+        count += 1;
+    }
+    count
+}
+
+/// Lexing plus `Token::into_owned` on every token, i.e. the extra cost of keeping tokens past the source.
+fn lex_all_into_owned(source: &str) -> usize {
+    let mut lexer = Lexer::new(source);
+    let mut count = 0;
+    loop {
+        let token = lexer.next_token().into_owned();
+        if token.kind == TokenKind::EOF {
+            break count;
+        }
+        count += 1;
+    }
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let page = generated_page(2_000);
+
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(page.len() as u64));
+    group.bench_function("borrowed", |b| b.iter(|| lex_all(black_box(&page))));
+    group.bench_function("borrowed+into_owned", |b| b.iter(|| lex_all_into_owned(black_box(&page))));
+    group.finish();
+
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(page.len() as u64));
+    group.bench_function("parse_document", |b| {
+        b.iter(|| Parser::new(Lexer::new(black_box(&page))).parse_document().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer);
+criterion_main!(benches);
@@ -2,9 +2,273 @@
 pub enum Node {
     Element {
         tag_name: String,
-        attributes: Vec<(String, String)>,
-        class_names: Vec<String>,
+        attributes: Vec<Attribute>,
+        class_names: Vec<ClassName>,
         children: Vec<Node>,
+        /// Identity among its siblings, set from a keyed `{#each}`; otherwise the position is used
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
     },
-    Text(String),
+    Text {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// `<>...</>` - groups siblings without a wrapping element
+    Fragment(Vec<Node>),
+    /// `<!-- ... -->` or `{/* ... */}`, only kept when the parser is asked to
+    Comment {
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// `{user.name}` in text; `expression` is the source between the braces
+    Expression {
+        expression: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// `<script>` or `<style>`, whose content is kept verbatim instead of parsed as markup
+    RawText {
+        tag_name: String,
+        attributes: Vec<Attribute>,
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// `<Card title="x">...</Card>` - a capitalised tag, expanded from a component's `.dts` file;
+    /// `name` may be qualified with a package alias, as in `MUI:Button`
+    Component {
+        name: String,
+        attributes: Vec<Attribute>,
+        /// Content passed to the component, which fills the `<slot>`s in its body
+        children: Vec<Node>,
+        /// The nodes the component renders, once the engine has expanded it with its props
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<Vec<Node>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// `{#if cond}...{:else if cond}...{:else}...{/if}` - the first branch whose condition
+    /// holds is rendered, or `otherwise` when none do
+    If {
+        branches: Vec<IfBranch>,
+        otherwise: Option<Vec<Node>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+    /// `{#each items as item, i (item.id)}...{:else}...{/each}` - renders `children` once per item
+    /// with `item` (and `index`, if named) in scope, or `otherwise` when there are no items
+    Each {
+        /// Expression source for the list
+        items: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        items_span: Option<Span>,
+        item: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<String>,
+        /// Expression source giving each item's identity, written in parentheses
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key_span: Option<Span>,
+        children: Vec<Node>,
+        otherwise: Option<Vec<Node>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+}
+
+/// One `{#if}` or `{:else if}` branch of a `Node::If`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IfBranch {
+    /// Expression source; the span covers just the condition
+    pub condition: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition_span: Option<Span>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// A text node with no source location.
+    pub fn text(content: &str) -> Node {
+        Node::Text { content: content.to_string(), span: None }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Node::Element { span, .. }
+            | Node::Text { span, .. }
+            | Node::Comment { span, .. }
+            | Node::Expression { span, .. }
+            | Node::RawText { span, .. }
+            | Node::Component { span, .. }
+            | Node::If { span, .. }
+            | Node::Each { span, .. } => *span,
+            Node::Fragment(_) => None,
+        }
+    }
+}
+
+/// Location of a node in its `.dts` source.
+/// Lines are 1-based, columns are 0-based; the end is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Span {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+impl Span {
+    /// A span from the start of `self` to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start_line: self.start_line,
+            start_column: self.start_column,
+            end_line: other.end_line,
+            end_column: other.end_column,
+            start_offset: self.start_offset,
+            end_offset: other.end_offset,
+        }
+    }
+
+    /// The span without its first and last characters, e.g. the text between a pair of braces.
+    pub fn inner(self) -> Span {
+        Span {
+            start_column: self.start_column + 1,
+            end_column: self.end_column.saturating_sub(1),
+            start_offset: self.start_offset + 1,
+            end_offset: self.end_offset.saturating_sub(1),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Attribute {
+    pub name: String,
+    pub value: AttributeValue,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+impl Attribute {
+    pub fn new(name: &str, value: AttributeValue) -> Self {
+        Self { name: name.to_string(), value, span: None }
+    }
+}
+
+/// A single class from an element's `class` attribute.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClassName {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+impl ClassName {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), span: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AttributeValue {
+    /// Valueless attribute such as `<input disabled>`
+    Boolean,
+    /// Quoted (`name="value"`) or unquoted (`name=value`) value
+    Literal(String),
+    /// Value with expressions in it, such as `class="p-{size}"` or `href={url}`
+    Interpolated(Vec<AttributePart>),
+}
+
+impl AttributeValue {
+    /// The attribute's text, or `None` for boolean attributes and interpolated values,
+    /// which only have text once their expressions are evaluated.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttributeValue::Boolean | AttributeValue::Interpolated(_) => None,
+            AttributeValue::Literal(value) => Some(value),
+        }
+    }
+
+    /// The attribute's text, or `None` for boolean attributes.
+    /// Interpolated values give their template, braces included.
+    pub fn into_value(self) -> Option<String> {
+        match self {
+            AttributeValue::Boolean => None,
+            AttributeValue::Literal(value) => Some(value),
+            AttributeValue::Interpolated(parts) => Some(
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        AttributePart::Literal(text) => text.clone(),
+                        AttributePart::Expression { expression, .. } => format!("{{{}}}", expression),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A piece of an interpolated attribute value.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum AttributePart {
+    Literal(String),
+    /// `expression` is the source between the braces; the span covers the braces too
+    Expression {
+        expression: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        span: Option<Span>,
+    },
+}
+
+/// HTML void elements never have children or a closing tag.
+pub const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input",
+    "link", "meta", "param", "source", "track", "wbr",
+];
+
+pub fn is_void_element(tag_name: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+}
+
+/// Tags naming a component rather than an HTML element start with a capital letter,
+/// after any `Alias:` prefix.
+pub fn is_component_tag(tag_name: &str) -> bool {
+    tag_name
+        .rsplit(':')
+        .next()
+        .and_then(|name| name.chars().next())
+        .is_some_and(|c| c.is_uppercase())
+}
+
+/// Marks where a component's body puts the content it is given: `<slot />` for the default slot,
+/// `<slot name="sidebar">` for a named one. Its children are fallback content for when nothing is given.
+pub const SLOT_ELEMENT: &str = "slot";
+
+/// Attribute sending an element passed to a component to a named slot, as in `<nav slot="sidebar">`.
+pub const SLOT_ATTRIBUTE: &str = "slot";
+
+/// Elements whose text is kept exactly as written instead of having its whitespace collapsed.
+pub const PRESERVE_WHITESPACE_ELEMENTS: &[&str] = &["pre", "textarea"];
+
+/// Boolean attribute that opts any element, and everything inside it, out of whitespace collapsing.
+pub const PRESERVE_WHITESPACE_ATTRIBUTE: &str = "preserve-whitespace";
+
+pub fn preserves_whitespace(tag_name: &str, attributes: &[Attribute]) -> bool {
+    PRESERVE_WHITESPACE_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
+        || attributes.iter().any(|attr| attr.name == PRESERVE_WHITESPACE_ATTRIBUTE)
+}
+
+/// Elements whose content is raw text up to the matching closing tag, e.g. CSS or JS.
+pub const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+pub fn is_raw_text_element(tag_name: &str) -> bool {
+    RAW_TEXT_ELEMENTS.contains(&tag_name.to_ascii_lowercase().as_str())
 }
@@ -0,0 +1,243 @@
+use crate::engine::runtime::RuntimeError;
+use crate::error::DesignTimeError;
+use crate::Span;
+use owo_colors::{OwoColorize, Style};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem in a file, as data: rendered for terminals by `render`, or serialised for tools.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub span: Option<Span>,
+    pub suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn from_error(error: &DesignTimeError, file: Option<&str>) -> Self {
+        Self {
+            file: file.map(str::to_string),
+            severity: Severity::Error,
+            code: error.code().to_string(),
+            message: error.message(),
+            span: error.span(),
+            suggestions: error.suggestion().map(str::to_string).into_iter().collect(),
+        }
+    }
+
+    pub fn from_runtime_error(error: &RuntimeError, file: Option<&str>) -> Self {
+        Self {
+            file: error.file.clone().or_else(|| file.map(str::to_string)),
+            severity: Severity::Error,
+            code: "E0400".to_string(),
+            message: error.message.clone(),
+            span: error.span,
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// One line of JSON, for `--message-format=json`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Diagnostic is always serialisable")
+    }
+
+    /// Renders the diagnostic the way a compiler would: a header with its code, the file location,
+    /// the offending source lines with the span underlined, and each suggestion as `help:`.
+    ///
+    /// ```text
+    /// error[E0200]: Mismatched closing tag: expected </p>, got </span>
+    ///  --> src/pages/index.page.dts:1:13
+    ///   |
+    /// 1 | <div><p>hi</span></div>
+    ///   |             ^^^^
+    ///   |
+    ///   = help: Change the closing tag to match the opening tag: </p>
+    /// ```
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let styles = Styles::new(color, self.severity);
+        let mut out = String::new();
+        let file = self.file.as_deref().unwrap_or("<input>");
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        let _ = writeln!(
+            out,
+            "{}{}",
+            format!("{}[{}]", severity, self.code).style(styles.severity),
+            format!(": {}", self.message).style(styles.bold),
+        );
+
+        let Some(span) = self.span else {
+            let _ = writeln!(out, "  {} {}", "-->".style(styles.gutter), file);
+            for suggestion in &self.suggestions {
+                let _ = writeln!(out, "  {} {} {}", "=".style(styles.gutter), "help:".style(styles.help), suggestion);
+            }
+            return out;
+        };
+
+        let gutter_width = span.end_line.to_string().len();
+        let pad = " ".repeat(gutter_width);
+        let _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            pad,
+            "-->".style(styles.gutter),
+            file,
+            span.start_line,
+            span.start_column + 1,
+        );
+        let _ = writeln!(out, "{} {}", pad, "|".style(styles.gutter));
+
+        for (line_no, line) in snippet_lines(source, span) {
+            let (from, to) = underline_range(line, line_no, span);
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                format!("{:>width$}", line_no, width = gutter_width).style(styles.gutter),
+                "|".style(styles.gutter),
+                line,
+            );
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                pad,
+                "|".style(styles.gutter),
+                " ".repeat(from),
+                "^".repeat(to - from).style(styles.severity),
+            );
+        }
+
+        if !self.suggestions.is_empty() {
+            let _ = writeln!(out, "{} {}", pad, "|".style(styles.gutter));
+        }
+        for suggestion in &self.suggestions {
+            let _ = writeln!(out, "{} {} {} {}", pad, "=".style(styles.gutter), "help:".style(styles.help), suggestion);
+        }
+
+        out
+    }
+}
+
+/// Renders `error` from `file_name` for a terminal; see `Diagnostic::render`.
+pub fn render_diagnostic(error: &DesignTimeError, file_name: &str, source: &str, color: bool) -> String {
+    Diagnostic::from_error(error, Some(file_name)).render(source, color)
+}
+
+struct Styles {
+    severity: Style,
+    help: Style,
+    gutter: Style,
+    bold: Style,
+}
+
+impl Styles {
+    fn new(color: bool, severity: Severity) -> Self {
+        if color {
+            Self {
+                severity: match severity {
+                    Severity::Error => Style::new().red().bold(),
+                    Severity::Warning => Style::new().yellow().bold(),
+                },
+                help: Style::new().cyan().bold(),
+                gutter: Style::new().blue().bold(),
+                bold: Style::new().bold(),
+            }
+        } else {
+            Self { severity: Style::new(), help: Style::new(), gutter: Style::new(), bold: Style::new() }
+        }
+    }
+}
+
+/// The source lines covered by `span`, with their 1-based line numbers.
+fn snippet_lines(source: &str, span: Span) -> Vec<(usize, &str)> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .skip(span.start_line.saturating_sub(1))
+        .take(span.end_line.saturating_sub(span.start_line) + 1)
+        .collect()
+}
+
+/// Column range to underline on one line of a (possibly multi-line) span; always at least one caret.
+fn underline_range(line: &str, line_no: usize, span: Span) -> (usize, usize) {
+    let line_len = line.chars().count();
+    let from = if line_no == span.start_line { span.start_column } else { 0 };
+    let to = if line_no == span.end_line { span.end_column } else { line_len };
+    let from = from.min(line_len);
+    (from, to.max(from + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser};
+
+    #[test]
+    fn test_render_parser_error_with_snippet() {
+        let source = "<main>\n<div><p>hi</span></div>\n</main>";
+        let err = Parser::new(Lexer::new(source)).parse_document().unwrap_err();
+
+        assert_eq!(render_diagnostic(&err, "index.page.dts", source, false), "\
+error[E0200]: Mismatched closing tag: expected </p>, got </span>
+ --> index.page.dts:2:13
+  |
+2 | <div><p>hi</span></div>
+  |             ^^^^
+  |
+  = help: Change the closing tag to match the opening tag: </p>
+");
+    }
+
+    #[test]
+    fn test_render_error_without_span() {
+        let err = DesignTimeError::RuntimeError("boom".to_string());
+
+        assert_eq!(render_diagnostic(&err, "index.page.dts", "", false), "error[E0400]: boom\n  --> index.page.dts\n");
+    }
+
+    #[test]
+    fn test_diagnostic_json_line() {
+        let source = "<div class=>";
+        let err = Parser::new(Lexer::new(source)).parse_document().unwrap_err();
+        let json = Diagnostic::from_error(&err, Some("a.page.dts")).to_json();
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(!json.contains('\n'));
+        assert_eq!(value["file"], "a.page.dts");
+        assert_eq!(value["severity"], "error");
+        assert_eq!(value["code"], "E0200");
+        assert_eq!(value["message"], "Expected attribute value, got Gt");
+        assert_eq!(value["span"]["start_offset"], 11);
+        assert_eq!(value["suggestions"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_config_error_diagnostic() {
+        let err = DesignTimeError::ConfigError {
+            path: "/devServer/port".to_string(),
+            message: "0 is less than the minimum of 1".to_string(),
+        };
+        let diagnostic = Diagnostic::from_error(&err, Some("designtime.json"));
+
+        assert_eq!(diagnostic.code, "E0600");
+        assert_eq!(diagnostic.span, None);
+        assert_eq!(
+            diagnostic.render("", false),
+            "error[E0600]: /devServer/port: 0 is less than the minimum of 1\n  --> designtime.json\n",
+        );
+    }
+}
@@ -5,7 +5,8 @@ use std::fmt;
 pub enum DomNode {
     Element {
         tag: String,
-        attributes: Vec<(String, String)>,
+        /// `None` marks a boolean attribute such as `disabled`
+        attributes: Vec<(String, Option<String>)>,
         class_names: Vec<String>,
         children: Vec<DomNode>,
         key: String,
@@ -39,7 +40,7 @@ impl DomNode {
 
 pub struct DomNodeBuilder {
     tag: String,
-    attributes: Vec<(String, String)>,
+    attributes: Vec<(String, Option<String>)>,
     class_names: Vec<String>,
     children: Vec<DomNode>,
     key: Option<String>,
@@ -48,7 +49,13 @@ pub struct DomNodeBuilder {
 
 impl DomNodeBuilder {
     pub fn attr(mut self, name: &str, value: &str) -> Self {
-        self.attributes.push((name.to_string(), value.to_string()));
+        self.attributes.push((name.to_string(), Some(value.to_string())));
+        self
+    }
+
+    /// Add a valueless attribute such as `disabled`.
+    pub fn bool_attr(mut self, name: &str) -> Self {
+        self.attributes.push((name.to_string(), None));
         self
     }
 
@@ -72,7 +79,7 @@ impl DomNodeBuilder {
         self
     }
 
-    pub fn attributes(mut self, attrs: Vec<(String, String)>) -> Self {
+    pub fn attributes(mut self, attrs: Vec<(String, Option<String>)>) -> Self {
         self.attributes.extend(attrs);
         self
     }
@@ -111,7 +118,10 @@ impl fmt::Display for DomNode {
                     if !attributes.is_empty() {
                         writeln!(f, "{}  Attributes:", indent_str)?;
                         for (name, value) in attributes {
-                            writeln!(f, "{}    {} = \"{}\"", indent_str, name, value)?;
+                            match value {
+                                Some(value) => writeln!(f, "{}    {} = \"{}\"", indent_str, name, value)?,
+                                None => writeln!(f, "{}    {}", indent_str, name)?,
+                            }
                         }
                     }
                     if !class_names.is_empty() {
@@ -4,6 +4,6 @@ pub mod html_mac;
 
 pub mod prelude {
     pub use crate::dom::DomNode;
-    pub use crate::transform::{transform, get_css};
+    pub use crate::transform::{transform, transform_page, get_css, get_scripts, Page};
     pub use crate::html_mac::*;
 }
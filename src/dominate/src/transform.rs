@@ -1,70 +1,249 @@
 use crate::dom::DomNode;
-use designtime_ast::Node;
+use designtime_ast::{Node, Span, PRESERVE_WHITESPACE_ATTRIBUTE, SLOT_ATTRIBUTE, SLOT_ELEMENT};
 use styleman::StyleMan;
-use lazy_static::lazy_static;
-use std::sync::Mutex;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-lazy_static! {
-    static ref STYLEMAN: Mutex<StyleMan> = Mutex::new(StyleMan::new());
+// Per thread, so transforms running side by side don't see each other's CSS and scripts
+thread_local! {
+    static STYLEMAN: RefCell<StyleMan> = RefCell::new(StyleMan::new());
+    static SCRIPTS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A transformed page: its DOM, plus the CSS and inline scripts taken out of it.
+#[derive(Debug)]
+pub struct Page {
+    pub nodes: Vec<DomNode>,
+    /// The page's classes and `<style>` blocks
+    pub styles: StyleMan,
+    /// Inline `<script>` contents, in document order
+    pub scripts: Vec<String>,
 }
 
 /// Transform AST nodes into DomNodes while accumulating CSS classes.
+/// The CSS and scripts are kept for `get_css` and `get_scripts`.
 pub fn transform(nodes: Vec<Node>) -> Vec<DomNode> {
-    // Reset StyleMan before transform
-    {
-        let mut styleman = STYLEMAN.lock().expect("StyleMan poisoned");
-        *styleman = StyleMan::new();
-    }
+    let page = transform_page(nodes);
+    STYLEMAN.with(|styleman| *styleman.borrow_mut() = page.styles);
+    SCRIPTS.with(|scripts| *scripts.borrow_mut() = page.scripts);
+    page.nodes
+}
 
-    nodes.into_iter()
+/// Like `transform`, but returns the page's CSS and scripts instead of keeping them.
+pub fn transform_page(nodes: Vec<Node>) -> Page {
+    let mut styles = StyleMan::new();
+    let mut scripts = Vec::new();
+    let nodes = flatten_nodes(nodes).into_iter()
         .enumerate()
-        .map(|(idx, node)| transform_node(node, format!("node{}", idx)))
+        .filter_map(|(idx, node)| transform_node(node, format!("node{}", idx), &mut styles, &mut scripts))
+        .collect();
+    Page { nodes, styles, scripts }
+}
+
+/// Splice the children of every fragment and the body of every expanded component into the
+/// surrounding sibling list, and drop comments, which never render.
+/// A component's body has its slots filled first; a `<slot>` left outside any component shows its fallback.
+/// Content that no slot takes was already reported by `check_slots` when the component was expanded.
+pub fn flatten_nodes(nodes: Vec<Node>) -> Vec<Node> {
+    nodes.into_iter()
+        .flat_map(|node| match node {
+            Node::Fragment(children) => flatten_nodes(children),
+            Node::Comment { .. } => Vec::new(),
+            Node::Component { children, body: Some(body), .. } => flatten_nodes(fill_content(body, children)),
+            Node::Element { tag_name, children, .. } if tag_name == SLOT_ELEMENT => flatten_nodes(children),
+            other => vec![other],
+        })
         .collect()
 }
 
-fn transform_node(node: Node, key: String) -> DomNode {
+/// Puts the content passed to a component into the `<slot>`s of its body. Elements with a
+/// `slot="name"` attribute go to `<slot name="name">` and everything else to the default `<slot />`;
+/// a slot given nothing keeps its own children as fallback content.
+/// Content that none of the slots would take is an error rather than being dropped.
+pub fn fill_slots(body: Vec<Node>, content: Vec<Node>) -> Result<Vec<Node>, UnusedSlotContent> {
+    check_slots(&body, &content)?;
+    Ok(fill_content(body, content))
+}
+
+/// Content for a slot that a body doesn't have, like `<nav slot="sidebar">` passed to a
+/// component with only `<slot name="side" />`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusedSlotContent {
+    /// The slot the content asks for, or `None` for the default `<slot />`
+    pub slot: Option<String>,
+    /// The content that no slot takes
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for UnusedSlotContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.slot {
+            Some(name) => write!(f, "has no <slot name=\"{}\"> for this content", name),
+            None => write!(f, "has no <slot /> for this content"),
+        }
+    }
+}
+
+/// Checks that every piece of `content` has a `<slot>` in `body` to go in, without filling anything.
+/// Whitespace and comments don't need a slot.
+pub fn check_slots(body: &[Node], content: &[Node]) -> Result<(), UnusedSlotContent> {
+    let mut names = HashSet::new();
+    slot_names(body, &mut names);
+    for node in content.iter().filter(|node| !is_blank(node)) {
+        let slot = match node {
+            Node::Element { attributes, .. } => attributes.iter()
+                .find(|attr| attr.name == SLOT_ATTRIBUTE)
+                .and_then(|attr| attr.value.as_str()),
+            _ => None,
+        };
+        if !names.contains(slot.unwrap_or_default()) {
+            return Err(UnusedSlotContent { slot: slot.map(str::to_string), span: content_span(node) });
+        }
+    }
+    Ok(())
+}
+
+fn is_blank(node: &Node) -> bool {
+    match node {
+        Node::Text { content, .. } => content.trim().is_empty(),
+        Node::Comment { .. } => true,
+        Node::Fragment(children) => children.iter().all(is_blank),
+        _ => false,
+    }
+}
+
+/// Fragments have no span of their own, so they point at their first piece of content.
+fn content_span(node: &Node) -> Option<Span> {
     match node {
-        Node::Text(text) => {
-            if let Some(expr) = extract_expression(&text) {
-                DomNode::expression(&expr)
-            } else {
-                DomNode::text(&text)
+        Node::Fragment(children) => children.iter().find(|child| !is_blank(child)).and_then(content_span),
+        other => other.span(),
+    }
+}
+
+/// Collects the names of the slots `fill` would put content in, `""` being the default slot.
+fn slot_names<'a>(nodes: &'a [Node], names: &mut HashSet<&'a str>) {
+    for node in nodes {
+        match node {
+            Node::Element { tag_name, attributes, children, .. } => {
+                if tag_name == SLOT_ELEMENT {
+                    names.insert(attributes.iter()
+                        .find(|attr| attr.name == "name")
+                        .and_then(|attr| attr.value.as_str())
+                        .unwrap_or_default());
+                }
+                slot_names(children, names);
             }
+            Node::Fragment(children) | Node::Component { children, .. } => slot_names(children, names),
+            _ => {}
         }
-        Node::Element { tag_name, attributes, class_names, children } => {
-            // Remove "class" attr from attributes vector
-            let mut filtered_attrs = attributes.clone();
-            filtered_attrs.retain(|(name, _)| name != "class");
-
-            if !class_names.is_empty() {
-                let mut styleman = STYLEMAN.lock().expect("StyleMan poisoned");
-                styleman.add_classes(class_names.clone());
+    }
+}
+
+fn fill_content(body: Vec<Node>, content: Vec<Node>) -> Vec<Node> {
+    let mut slots: HashMap<String, Vec<Node>> = HashMap::new();
+    for node in content {
+        let (name, node) = match node {
+            Node::Element { tag_name, mut attributes, class_names, children, key, span } => {
+                let name = attributes.iter()
+                    .position(|attr| attr.name == SLOT_ATTRIBUTE)
+                    .and_then(|i| attributes.remove(i).value.into_value());
+                (name, Node::Element { tag_name, attributes, class_names, children, key, span })
             }
+            other => (None, other),
+        };
+        slots.entry(name.unwrap_or_default()).or_default().push(node);
+    }
+    // Whitespace between named content isn't content for the default slot
+    if let Some(default) = slots.get("")
+        && default.iter().all(|node| matches!(node, Node::Text { content, .. } if content.trim().is_empty()))
+    {
+        slots.remove("");
+    }
+    fill(body, &mut slots)
+}
+
+fn fill(nodes: Vec<Node>, slots: &mut HashMap<String, Vec<Node>>) -> Vec<Node> {
+    nodes.into_iter()
+        .map(|node| match node {
+            Node::Element { tag_name, attributes, children, .. } if tag_name == SLOT_ELEMENT => {
+                let name = attributes.iter()
+                    .find(|attr| attr.name == "name")
+                    .and_then(|attr| attr.value.as_str())
+                    .unwrap_or_default();
+                match slots.remove(name) {
+                    Some(content) => Node::Fragment(content),
+                    None => Node::Fragment(fill(children, slots)),
+                }
+            }
+            Node::Element { tag_name, attributes, class_names, children, key, span } => {
+                Node::Element { tag_name, attributes, class_names, children: fill(children, slots), key, span }
+            }
+            Node::Fragment(children) => Node::Fragment(fill(children, slots)),
+            // Content passed to a nested component can hold this component's slots; its body's slots are its own
+            Node::Component { name, attributes, children, body, span } => {
+                Node::Component { name, attributes, children: fill(children, slots), body, span }
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// Returns `None` for nodes that are taken out of the tree, like inline `<style>` and `<script>`.
+fn transform_node(node: Node, key: String, styles: &mut StyleMan, scripts: &mut Vec<String>) -> Option<DomNode> {
+    let dom_node = match node {
+        Node::Text { content: text, .. } => DomNode::text(&text),
+        Node::Expression { expression, .. } => DomNode::expression(expression.trim()),
+        Node::RawText { tag_name, content, .. } if tag_name.eq_ignore_ascii_case("style") => {
+            styles.add_stylesheet(&content);
+            return None;
+        }
+        Node::RawText { content, .. } if !content.trim().is_empty() => {
+            scripts.push(content);
+            return None;
+        }
+        // An external `<script src="...">` stays in the page
+        Node::RawText { tag_name, attributes, .. } => DomNode::element(&tag_name)
+            .key(&key)
+            .attributes(attributes.into_iter().map(|attr| (attr.name, attr.value.into_value())).collect())
+            .build(),
+        Node::Element { tag_name, attributes, class_names, children, key: element_key, .. } => {
+            let key = element_key.unwrap_or(key);
+            // Remove "class" and the parser-only whitespace opt-out from attributes vector
+            let filtered_attrs = attributes.into_iter()
+                .filter(|attr| attr.name != "class" && attr.name != PRESERVE_WHITESPACE_ATTRIBUTE)
+                .map(|attr| (attr.name, attr.value.into_value()))
+                .collect();
+            let class_names: Vec<String> = class_names.into_iter().map(|c| c.name).collect();
+
+            styles.add_classes(class_names.clone());
 
             DomNode::element(&tag_name)
                 .key(&key)
                 .attributes(filtered_attrs)
                 .class_names(class_names)
-                .children(children.into_iter()
+                .children(flatten_nodes(children).into_iter()
                     .enumerate()
-                    .map(|(i, c)| transform_node(c, format!("{}-{}", key, i)))
+                    .filter_map(|(i, c)| transform_node(c, format!("{}-{}", key, i), styles, scripts))
                     .collect()
                 )
                 .build()
         }
-    }
+        // Blocks and components only render once the engine has expanded them against the page data
+        Node::Component { body: None, .. } | Node::If { .. } | Node::Each { .. } => return None,
+        Node::Fragment(_) | Node::Comment { .. } | Node::Component { .. } => {
+            unreachable!("removed by flatten_nodes before transform")
+        }
+    };
+    Some(dom_node)
 }
 
-fn extract_expression(text: &str) -> Option<String> {
-    let trimmed = text.trim();
-    if trimmed.starts_with("{{") && trimmed.ends_with("}}") {
-        Some(trimmed[2..trimmed.len() - 2].trim().to_string())
-    } else {
-        None
-    }
+/// CSS from the last `transform` on this thread.
+pub fn get_css() -> String {
+    STYLEMAN.with(|styleman| styleman.borrow().generate_css())
 }
 
-pub fn get_css() -> String {
-    let styleman = STYLEMAN.lock().expect("StyleMan poisoned");
-    styleman.generate_css()
+/// Inline `<script>` contents from the last `transform` on this thread, in document order.
+pub fn get_scripts() -> Vec<String> {
+    SCRIPTS.with(|scripts| scripts.borrow().clone())
 }
@@ -0,0 +1,283 @@
+// Static builds: every routed page rendered to `build.outputDir` as an HTML document, plus one stylesheet
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::engine::router::{Route, RouteMatch, Router, Segment};
+use crate::engine::runtime::Runtime;
+use crate::error::DesignTimeError;
+use crate::{Lexer, Parser};
+use dominate::prelude::*;
+use render_core::render_html;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use styleman::StyleMan;
+
+/// Where the site is written when designtime.json doesn't set `build.outputDir`.
+pub const DEFAULT_OUTPUT_DIR: &str = "dist";
+
+/// The stylesheet every page links to, at the root of the output directory.
+pub const STYLESHEET_FILE: &str = "styles.css";
+
+/// What happened to one page of a build.
+#[derive(Debug)]
+pub struct PageReport {
+    /// The route's URL
+    pub path: String,
+    pub file: PathBuf,
+    /// The HTML file written, `None` when the page failed or was skipped
+    pub output: Option<PathBuf>,
+    pub duration: Duration,
+    /// Errors that stopped the page, or a warning when it was skipped
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug)]
+pub struct BuildReport {
+    pub output_dir: PathBuf,
+    /// Problems with the `build` settings themselves, like options that aren't supported yet
+    pub warnings: Vec<Diagnostic>,
+    pub pages: Vec<PageReport>,
+    pub stylesheet: PathBuf,
+    pub duration: Duration,
+}
+
+impl PageReport {
+    /// Whether the page had errors; a skipped page only has a warning.
+    pub fn failed(&self) -> bool {
+        self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl BuildReport {
+    pub fn failed(&self) -> impl Iterator<Item = &PageReport> {
+        self.pages.iter().filter(|page| page.failed())
+    }
+}
+
+/// Builds the site into `build.outputDir`, relative to `root`.
+///
+/// Every route is parsed, wrapped in its layouts and rendered to a full HTML document: `/` becomes
+/// `index.html`, `/blog/hello` becomes `blog/hello/index.html` and the 404 page `404.html`. The CSS
+/// of all pages goes into one `styles.css`, which every page links to, and each page's inline
+/// `<script>`s are written at the end of its `<body>`. With `build.minify` the stylesheet is minified.
+///
+/// A page that fails is reported and left out; the rest are still built. Routes with `[param]`
+/// segments are skipped, as the build has no values for them. Only failing to write the output is an error.
+/// `build.target` and `build.sourceMaps` aren't supported yet and are reported as warnings when set.
+pub fn build(runtime: &mut Runtime, router: &Router, root: &Path) -> Result<BuildReport, DesignTimeError> {
+    let started = Instant::now();
+    let config = runtime.workspace.build.as_ref();
+    let output_dir = root.join(config.and_then(|build| build.output_dir.as_deref()).unwrap_or(DEFAULT_OUTPUT_DIR));
+    let minify = config.and_then(|build| build.minify).unwrap_or(false);
+    let title = runtime.workspace.project.name.clone();
+    let mut warnings = Vec::new();
+    if config.and_then(|build| build.source_maps) == Some(true) {
+        warnings.push(unsupported("/build/sourceMaps", "Source maps aren't supported yet, so none are written"));
+    }
+    if config.and_then(|build| build.target.as_ref()).is_some_and(|target| !target.is_empty()) {
+        warnings.push(unsupported("/build/target", "Build targets aren't supported yet, so pages are built for the web"));
+    }
+    std::fs::create_dir_all(&output_dir)?;
+
+    let mut styles = StyleMan::new();
+    let mut pages = Vec::new();
+    let not_found = router.not_found().map(|route| (route, output_dir.join("404.html")));
+    let routes = router.routes().iter().map(|route| (route, output_dir.join(output_path(&route.path))));
+    for (route, output) in routes.chain(not_found) {
+        let started = Instant::now();
+        let (output, diagnostics) = if route.segments.iter().any(|segment| !matches!(segment, Segment::Static(_))) {
+            let message = format!("Skipped '{}': a static build has no values for its parameters", route.path);
+            (None, vec![warning(&DesignTimeError::CompilerError(message), &route.file.display().to_string())])
+        } else {
+            match render_page(runtime, router, route, &title) {
+                Ok((html, page_styles)) => {
+                    styles.merge(&page_styles);
+                    if let Some(directory) = output.parent() {
+                        std::fs::create_dir_all(directory)?;
+                    }
+                    std::fs::write(&output, html)?;
+                    (Some(output), Vec::new())
+                }
+                Err(diagnostics) => (None, diagnostics),
+            }
+        };
+        pages.push(PageReport { path: route.path.clone(), file: route.file.clone(), output, duration: started.elapsed(), diagnostics });
+    }
+
+    let stylesheet = output_dir.join(STYLESHEET_FILE);
+    let css = styles.generate_css();
+    std::fs::write(&stylesheet, if minify { minify_css(&css) } else { css })?;
+    Ok(BuildReport { output_dir, warnings, pages, stylesheet, duration: started.elapsed() })
+}
+
+fn warning(error: &DesignTimeError, file: &str) -> Diagnostic {
+    Diagnostic { severity: Severity::Warning, ..Diagnostic::from_error(error, Some(file)) }
+}
+
+fn unsupported(path: &str, message: &str) -> Diagnostic {
+    warning(&DesignTimeError::ConfigError { path: path.to_string(), message: message.to_string() }, "designtime.json")
+}
+
+/// Reads, parses and renders one page to HTML, with the CSS it needs, or the diagnostics for whatever went wrong.
+fn render_page(runtime: &mut Runtime, router: &Router, route: &Route, title: &str) -> Result<(String, StyleMan), Vec<Diagnostic>> {
+    let file = route.file.display().to_string();
+    let source = std::fs::read_to_string(&route.file).map_err(|err| vec![Diagnostic::from_error(&err.into(), Some(&file))])?;
+    let (nodes, errors) = Parser::new(Lexer::new(&source)).parse_document_recovering();
+    if !errors.is_empty() {
+        return Err(errors.iter().map(|err| Diagnostic::from_error(err, Some(&file))).collect());
+    }
+
+    let data = RouteMatch { route, params: Map::new() }.data(&Value::Object(Map::new()));
+    let page = runtime
+        .process_page(nodes, &router.layouts(route), &data)
+        .map_err(|err| vec![Diagnostic::from_runtime_error(&err, Some(&file))])?;
+    let html = format!("<!DOCTYPE html>\n{}\n", render_html(&document(page.nodes, title, page.scripts)));
+    Ok((html, page.styles))
+}
+
+/// The file a route is written to, inside the output directory.
+fn output_path(path: &str) -> PathBuf {
+    Path::new(path.trim_start_matches('/')).join("index.html")
+}
+
+/// Puts a page into an HTML document linking the stylesheet. A page that renders its own `<html>`
+/// or `<body>`, e.g. through a layout, is used as it is, with the stylesheet and scripts added.
+fn document(mut nodes: Vec<DomNode>, title: &str, scripts: Vec<String>) -> Vec<DomNode> {
+    let link = DomNode::element("link").attr("rel", "stylesheet").attr("href", &format!("/{}", STYLESHEET_FILE)).build();
+    let scripts: Vec<DomNode> = scripts.iter().map(|script| DomNode::element("script").child(DomNode::text(script)).build()).collect();
+    let head = |children: Vec<DomNode>| {
+        DomNode::element("head")
+            .child(DomNode::element("meta").attr("charset", "utf-8").build())
+            .child(DomNode::element("meta").attr("name", "viewport").attr("content", "width=device-width, initial-scale=1").build())
+            .child(DomNode::element("title").child(DomNode::text(title)).build())
+            .children(children)
+            .build()
+    };
+
+    if let Some(children) = find_element(&mut nodes, "html") {
+        match find_element(children, "head") {
+            Some(head) => head.push(link),
+            None => children.insert(0, head(vec![link])),
+        }
+        match find_element(children, "body") {
+            Some(body) => body.extend(scripts),
+            None => children.extend(scripts),
+        }
+        return nodes;
+    }
+
+    let body = match find_element(&mut nodes, "body") {
+        Some(body) => {
+            body.extend(scripts);
+            nodes
+        }
+        None => vec![DomNode::element("body").children(nodes).children(scripts).build()],
+    };
+    vec![DomNode::element("html").attr("lang", "en").child(head(vec![link])).children(body).build()]
+}
+
+/// The children of the first element among `nodes` with the tag `tag`.
+fn find_element<'a>(nodes: &'a mut [DomNode], tag: &str) -> Option<&'a mut Vec<DomNode>> {
+    nodes.iter_mut().find_map(|node| match node {
+        DomNode::Element { tag: name, children, .. } if name == tag => Some(children),
+        _ => None,
+    })
+}
+
+/// Drops the whitespace CSS doesn't need, leaving strings as they are.
+fn minify_css(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut quote = None;
+    let mut escaped = false;
+    let mut space = false;
+    for c in css.chars() {
+        if let Some(q) = quote {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                c if c == q => quote = None,
+                _ => {}
+            }
+            continue;
+        }
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        // `a :hover` and `a:hover` differ, so only the space after a `:` goes
+        if space && !out.is_empty() && !matches!(c, '{' | '}' | ';' | ',' | '>') && !out.ends_with(['{', '}', ';', ':', ',', '>']) {
+            out.push(' ');
+        }
+        space = false;
+        if c == '"' || c == '\'' {
+            quote = Some(c);
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::WorkspaceConfig;
+
+    #[test]
+    fn test_minify_css() {
+        let css = ".card h2 > p:hover,\n.a :first-child {\n  content: \"a  b\";\n  width: calc(1px + 2px);\n}\n";
+        assert_eq!(minify_css(css), ".card h2>p:hover,.a :first-child{content:\"a  b\";width:calc(1px + 2px);}");
+    }
+
+    #[test]
+    fn test_build_site() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for (file, source) in [
+            ("src/pages/_layout.dts", "<body><nav>Site</nav><slot /></body>"),
+            ("src/pages/index.page.dts", "<h1 class=\"p-4\">Home</h1><script>console.log(1 < 2)</script>"),
+            ("src/pages/blog/hello.page.dts", "<p>{missing.title}</p>"),
+            ("src/pages/blog/[slug].page.dts", "<p>{params.slug}</p>"),
+            ("src/pages/404.page.dts", "<p class=\"p-4 m-2 flex\">Not found</p>"),
+        ] {
+            let file = root.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, source).unwrap();
+        }
+
+        let config: WorkspaceConfig = serde_json::from_value(serde_json::json!({
+            "project": { "name": "Test", "version": "1.0.0" },
+            "routes": { "pagesDir": "src/pages", "notFoundPage": "src/pages/404.page.dts" },
+            "build": { "outputDir": "out", "sourceMaps": true, "minify": false }
+        }))
+        .unwrap();
+        let router = Router::load(&config, root).unwrap();
+        let mut runtime = Runtime::new(config);
+        let report = build(&mut runtime, &router, root).unwrap();
+
+        let read = |file: &str| std::fs::read_to_string(root.join("out").join(file));
+        let index = read("index.html").unwrap();
+        let not_found = read("404.html").unwrap();
+        let stylesheet = read(STYLESHEET_FILE);
+
+        assert_eq!(
+            index,
+            "<!DOCTYPE html>\n<html lang=\"en\"><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+<title>Test</title><link rel=\"stylesheet\" href=\"/styles.css\"></head><body><nav>Site</nav><h1 class=\"p-4\">Home</h1>\
+<script>console.log(1 < 2)</script></body></html>\n"
+        );
+        assert!(not_found.contains("<body><nav>Site</nav><p class=\"p-4 m-2 flex\">Not found</p></body>"));
+        // One rule per class across every page, in a stable order
+        assert_eq!(stylesheet.unwrap(), ".flex { display: flex; }\n.m-2 { margin: 0.5rem; }\n.p-4 { padding: 1rem; }\n");
+
+        assert_eq!(report.failed().map(|page| page.path.as_str()).collect::<Vec<_>>(), ["/blog/hello"]);
+        let page = |path: &str| report.pages.iter().find(|page| page.path == path).unwrap();
+        assert_eq!(page("/blog/hello").diagnostics[0].file, Some(root.join("src/pages/blog/hello.page.dts").display().to_string()));
+        assert!(page("/blog/hello").output.is_none());
+        assert_eq!(page("/blog/[slug]").diagnostics[0].severity, Severity::Warning);
+        assert_eq!(page("/blog/[slug]").diagnostics[0].code, "E0300");
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].message, "/build/sourceMaps: Source maps aren't supported yet, so none are written");
+        assert_eq!(page("/").output, Some(root.join("out/index.html")));
+    }
+}
@@ -0,0 +1,282 @@
+// Components: `.dts` files that pages use as capitalised tags, like `<Card title="x">`
+
+use crate::error::{DesignTimeError, FileError};
+use crate::workspace::WorkspaceConfig;
+use crate::{Lexer, Parser};
+use designtime_ast::{is_component_tag, AttributeValue, Node, Span};
+use styleman::{scope_attribute, scope_css};
+use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Element declaring a component's props, e.g. `<props title subtitle="Untitled" />`.
+/// A prop without a value is required; otherwise the value is its default.
+pub const PROPS_TAG: &str = "props";
+
+#[derive(Debug, Clone)]
+pub struct Prop {
+    pub name: String,
+    /// `None` when the prop is required
+    pub default: Option<AttributeValue>,
+    pub span: Option<Span>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub name: String,
+    /// The package the component comes from, or `None` for the project's own
+    pub package: Option<String>,
+    /// Source file, which errors in the component's body are reported against
+    pub path: String,
+    pub props: Vec<Prop>,
+    /// The nodes the component renders, with its props in scope
+    pub body: Vec<Node>,
+    /// Attribute put on every element the component renders when it has a `<style scoped>`,
+    /// which the scoped rules are rewritten to require
+    pub scope: Option<String>,
+}
+
+impl Component {
+    /// Parses a component's source: an optional `<props>` declaration and the nodes it renders.
+    /// Top-level `<style scoped>` blocks are rewritten so they only apply to this component's elements.
+    pub fn parse(name: &str, path: &str, source: &str) -> Result<Self, Vec<DesignTimeError>> {
+        let (nodes, errors) = Parser::new(Lexer::new(source)).parse_document_recovering();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut props = Vec::new();
+        let mut body = Vec::new();
+        let mut scope = None;
+        for node in nodes {
+            match node {
+                Node::RawText { tag_name, mut attributes, content, span }
+                    if tag_name.eq_ignore_ascii_case("style") && attributes.iter().any(|attr| attr.name == SCOPED_ATTRIBUTE) =>
+                {
+                    let attribute = scope.get_or_insert_with(|| scope_attribute(path));
+                    attributes.retain(|attr| attr.name != SCOPED_ATTRIBUTE);
+                    body.push(Node::RawText { tag_name, attributes, content: scope_css(&content, attribute), span });
+                }
+                Node::Element { tag_name, attributes, .. } if tag_name == PROPS_TAG => {
+                    props.extend(attributes.into_iter().map(|attr| Prop {
+                        name: attr.name,
+                        default: match attr.value {
+                            AttributeValue::Boolean => None,
+                            value => Some(value),
+                        },
+                        span: attr.span,
+                    }));
+                }
+                other => body.push(other),
+            }
+        }
+        Ok(Self { name: name.to_string(), package: None, path: path.to_string(), props, body, scope })
+    }
+}
+
+/// Boolean attribute making a component's `<style>` apply only to the component.
+pub const SCOPED_ATTRIBUTE: &str = "scoped";
+
+/// Every component a page can use, by package and name, plus the package aliases from
+/// `components.aliases` (`MUI` for `@designtime.core.ui.MUI`, used as `<MUI:Button>`).
+#[derive(Debug, Default)]
+pub struct ComponentRegistry {
+    components: HashMap<(Option<String>, String), Component>,
+    aliases: HashMap<String, String>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the registry from the workspace's `components` settings, with directories relative to `root`.
+    ///
+    /// Unless auto-registration is turned off, every `.dts` file under the configured directories is a
+    /// component named after the file, `Card.dts` being `<Card>`; pages (`*.page.dts`) are skipped.
+    /// A directory inside a package, one of the alias targets or dependencies, registers its components
+    /// under that package. Missing directories are skipped, as packages may not be installed yet.
+    pub fn load(config: &WorkspaceConfig, root: &Path) -> Result<Self, Vec<FileError>> {
+        let mut registry = Self::new();
+        let Some(components) = &config.components else {
+            return Ok(registry);
+        };
+        for (alias, package) in components.aliases.iter().flatten() {
+            registry.alias(alias, package);
+        }
+        if components.auto_register == Some(false) {
+            return Ok(registry);
+        }
+
+        let mut packages: Vec<&String> = registry.aliases.values().collect();
+        if let Some(dependencies) = config.packages.as_ref().and_then(|packages| packages.dependencies.as_ref()) {
+            packages.extend(dependencies);
+        }
+        let mut found = Vec::new();
+        let mut errors = Vec::new();
+        for directory in components.directories.iter().flatten() {
+            let package = Path::new(directory)
+                .components()
+                .find_map(|part| packages.iter().find(|package| part.as_os_str() == package.as_str()))
+                .map(|package| package.to_string());
+
+            let directory = root.join(directory);
+            if !directory.is_dir() {
+                continue;
+            }
+            for entry in WalkDir::new(&directory).sort_by_file_name() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        errors.push(file_error(&directory, String::new(), DesignTimeError::Unknown(err.to_string())));
+                        continue;
+                    }
+                };
+                let path = entry.path();
+                let file_name = entry.file_name().to_string_lossy();
+                let Some(name) = file_name.strip_suffix(".dts") else {
+                    continue;
+                };
+                if !entry.file_type().is_file() || name.ends_with(".page") {
+                    continue;
+                }
+
+                let source = match std::fs::read_to_string(path) {
+                    Ok(source) => source,
+                    Err(err) => {
+                        errors.push(file_error(path, String::new(), err.into()));
+                        continue;
+                    }
+                };
+                if !is_component_tag(name) {
+                    let message = format!("Component '{}' must start with a capital letter to be usable as a tag", name);
+                    errors.push(file_error(path, source, DesignTimeError::CompilerError(message)));
+                    continue;
+                }
+                match Component::parse(name, &path.display().to_string(), &source) {
+                    Ok(component) => found.push(Component { package: package.clone(), ..component }),
+                    Err(parse_errors) => {
+                        errors.extend(parse_errors.into_iter().map(|err| file_error(path, source.clone(), err)));
+                    }
+                }
+            }
+        }
+
+        for component in found {
+            let path = component.path.clone();
+            if let Err(err) = registry.register(component) {
+                errors.push(file_error(Path::new(&path), String::new(), err));
+            }
+        }
+        if errors.is_empty() { Ok(registry) } else { Err(errors) }
+    }
+
+    /// Adds a component, failing if its package already has one with the same name.
+    pub fn register(&mut self, component: Component) -> Result<(), DesignTimeError> {
+        let key = (component.package.clone(), component.name.clone());
+        if let Some(existing) = self.components.get(&key) {
+            return Err(DesignTimeError::CompilerError(format!(
+                "Component '{}' is defined in both {} and {}",
+                component.name, existing.path, component.path
+            )));
+        }
+        self.components.insert(key, component);
+        Ok(())
+    }
+
+    /// Lets `<alias:Name>` refer to the components of `package`.
+    pub fn alias(&mut self, alias: &str, package: &str) {
+        self.aliases.insert(alias.to_string(), package.to_string());
+    }
+
+    /// Finds the component a tag names. `Alias:Name` looks in the aliased package (or the package
+    /// written out in full); a plain `Name` prefers the project's own components, then a package's
+    /// if exactly one package has it.
+    pub fn resolve(&self, tag_name: &str) -> Option<&Component> {
+        if let Some((prefix, name)) = tag_name.rsplit_once(':') {
+            let package = self.aliases.get(prefix).map_or(prefix, String::as_str);
+            return self.components.get(&(Some(package.to_string()), name.to_string()));
+        }
+        if let Some(component) = self.components.get(&(None, tag_name.to_string())) {
+            return Some(component);
+        }
+        let mut packaged = self.components.values().filter(|component| component.name == tag_name);
+        match (packaged.next(), packaged.next()) {
+            (Some(component), None) => Some(component),
+            _ => None,
+        }
+    }
+}
+
+fn file_error(path: &Path, source: String, error: DesignTimeError) -> FileError {
+    FileError { path: path.display().to_string(), source, error }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::expression::Scope;
+    use crate::engine::template::evaluate_nodes;
+    use dominate::dom::DomNode;
+
+    #[test]
+    fn test_parse_props_and_resolve_aliases() {
+        let source = r#"<props title subtitle="Untitled" />
+<div class="card"><h2>{title}</h2><p>{subtitle}</p></div>"#;
+        let card = Component::parse("Card", "Card.dts", source).unwrap();
+        assert_eq!(card.props.len(), 2);
+        assert!(card.props[0].default.is_none());
+        assert_eq!(card.props[1].default, Some(AttributeValue::Literal("Untitled".to_string())));
+        assert!(matches!(&card.body[..], [Node::Element { tag_name, .. }] if tag_name == "div"));
+
+        let mut registry = ComponentRegistry::new();
+        registry.alias("MUI", "@designtime.core.ui.MUI");
+        registry.register(card.clone()).unwrap();
+        let button = Component { name: "Button".to_string(), package: Some("@designtime.core.ui.MUI".to_string()), ..card.clone() };
+        registry.register(button).unwrap();
+
+        assert_eq!(registry.resolve("Card").unwrap().path, "Card.dts");
+        assert!(registry.resolve("MUI:Button").is_some());
+        assert!(registry.resolve("@designtime.core.ui.MUI:Button").is_some());
+        assert!(registry.resolve("Button").is_some());
+        assert!(registry.resolve("MUI:Card").is_none());
+        assert!(registry.register(card).is_err());
+    }
+
+    #[test]
+    fn test_scoped_styles() {
+        let source = r#"<style scoped>
+.card h2::before, p:hover { color: red }
+@media (max-width: 600px) { p { margin: 0 } }
+@keyframes pulse { from { opacity: 0 } }
+</style>
+<div class="card"><h2>Hi</h2><p>There</p></div>"#;
+        let card = Component::parse("Card", "src/components/Card.dts", source).unwrap();
+        let scope = card.scope.clone().unwrap();
+        assert_eq!(scope, scope_attribute("src/components/Card.dts"));
+
+        let Node::RawText { attributes, content, .. } = card.body[0].clone() else { panic!("Expected the style") };
+        assert!(attributes.is_empty());
+        assert_eq!(
+            content.trim(),
+            format!(
+                ".card h2[{s}]::before, p:hover[{s}] {{ color: red }}\n@media (max-width: 600px) {{ p[{s}] {{ margin: 0 }} }}\n@keyframes pulse {{ from {{ opacity: 0 }} }}",
+                s = scope
+            )
+        );
+
+        let mut registry = ComponentRegistry::new();
+        registry.register(card).unwrap();
+        let nodes = Parser::new(Lexer::new("<main><Card /><Card /></main>")).parse_document().unwrap();
+        let nodes = evaluate_nodes(nodes, &Scope::new(&serde_json::json!({})), &registry).unwrap();
+        let dom = dominate::transform::transform(nodes);
+
+        let DomNode::Element { attributes: main_attributes, children, .. } = &dom[0] else { panic!("Expected <main>") };
+        assert!(main_attributes.is_empty());
+        assert!(matches!(&children[0], DomNode::Element { attributes, .. } if attributes.iter().any(|(name, _)| *name == scope)));
+        // Each use of the component brings its style; StyleMan keeps one copy
+        let css = dominate::transform::get_css();
+        assert!(css.contains(&format!("p:hover[{}]", scope)));
+        assert_eq!(css.matches("color: red").count(), 1);
+    }
+}
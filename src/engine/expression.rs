@@ -0,0 +1,818 @@
+// Expression language used inside `{...}` in templates
+
+use crate::engine::runtime::RuntimeError;
+use crate::Span;
+use serde_json::{Number, Value};
+
+/// Variables visible to an expression: the page data plus any locals bound by template blocks.
+pub struct Scope<'a> {
+    data: &'a Value,
+    locals: Vec<(String, Value)>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(data: &'a Value) -> Self {
+        Self { data, locals: Vec::new(), parent: None }
+    }
+
+    /// A nested scope whose locals shadow this one's.
+    pub fn child(&'a self) -> Scope<'a> {
+        Scope { data: self.data, locals: Vec::new(), parent: Some(self) }
+    }
+
+    pub fn set(&mut self, name: &str, value: Value) {
+        self.locals.retain(|(local, _)| local != name);
+        self.locals.push((name.to_string(), value));
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value)
+            .or_else(|| match self.parent {
+                Some(parent) => parent.lookup(name),
+                None => self.data.get(name),
+            })
+    }
+}
+
+/// A parsed `{...}` expression, ready to evaluate against a `Scope`.
+///
+/// `source` is the expression text, such as what sits between a `{...}`'s braces, and `origin`
+/// is where that text is in the page, which errors are reported relative to.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    source: String,
+    origin: Option<Span>,
+    root: Expr,
+}
+
+impl Expression {
+    pub fn parse(source: &str, origin: Option<Span>) -> Result<Self, RuntimeError> {
+        let mut parser = ExprParser { tokens: tokenize(source, origin)?, pos: 0, source, origin };
+        let root = parser.parse_expr()?;
+        let token = parser.peek();
+        if token.kind != Tok::Eof {
+            return Err(error_at(source, origin, token.start, token.end, format!("Unexpected {} in expression", token.kind.describe())));
+        }
+        Ok(Self { source: source.to_string(), origin, root })
+    }
+
+    pub fn evaluate(&self, scope: &Scope) -> Result<Value, RuntimeError> {
+        self.eval(&self.root, scope)
+    }
+
+    fn eval(&self, expr: &Expr, scope: &Scope) -> Result<Value, RuntimeError> {
+        match &expr.kind {
+            ExprKind::Literal(value) => Ok(value.clone()),
+            ExprKind::Array(items) => Ok(Value::Array(items.iter().map(|item| self.eval(item, scope)).collect::<Result<_, _>>()?)),
+            ExprKind::Object(entries) => {
+                let mut object = serde_json::Map::new();
+                for (key, value) in entries {
+                    object.insert(key.clone(), self.eval(value, scope)?);
+                }
+                Ok(Value::Object(object))
+            }
+            ExprKind::Variable(name) => scope
+                .lookup(name)
+                .cloned()
+                .ok_or_else(|| self.error(expr, format!("Unknown variable '{}'", name))),
+            ExprKind::Member(object, property) => {
+                let object = self.eval(object, scope)?;
+                self.member(expr, &object, &Value::String(property.clone()))
+            }
+            ExprKind::Index(object, index) => {
+                let object = self.eval(object, scope)?;
+                let index = self.eval(index, scope)?;
+                self.member(expr, &object, &index)
+            }
+            ExprKind::Not(operand) => Ok(Value::Bool(!truthy(&self.eval(operand, scope)?))),
+            ExprKind::Negate(operand) => {
+                let value = self.eval(operand, scope)?;
+                let n = self.number(operand, &value)?;
+                Ok(number(-n))
+            }
+            // `&&` and `||` short-circuit and give back an operand, as in JavaScript
+            ExprKind::Binary(BinaryOp::And, left, right) => {
+                let left = self.eval(left, scope)?;
+                if truthy(&left) { self.eval(right, scope) } else { Ok(left) }
+            }
+            ExprKind::Binary(BinaryOp::Or, left, right) => {
+                let left = self.eval(left, scope)?;
+                if truthy(&left) { Ok(left) } else { self.eval(right, scope) }
+            }
+            ExprKind::Binary(op, left, right) => {
+                let l = self.eval(left, scope)?;
+                let r = self.eval(right, scope)?;
+                self.binary(expr, *op, (left, l), (right, r))
+            }
+            ExprKind::Conditional(test, then, otherwise) => {
+                if truthy(&self.eval(test, scope)?) {
+                    self.eval(then, scope)
+                } else {
+                    self.eval(otherwise, scope)
+                }
+            }
+            ExprKind::Filter(value, name, args) => {
+                let value = self.eval(value, scope)?;
+                let args = args.iter().map(|arg| self.eval(arg, scope)).collect::<Result<Vec<_>, _>>()?;
+                self.filter(expr, name, value, &args)
+            }
+        }
+    }
+
+    fn member(&self, expr: &Expr, object: &Value, key: &Value) -> Result<Value, RuntimeError> {
+        match (object, key) {
+            (Value::Object(map), _) => Ok(map.get(&to_display_string(key)).cloned().unwrap_or(Value::Null)),
+            (Value::Array(items), Value::Number(n)) => {
+                Ok(n.as_u64().and_then(|i| items.get(i as usize)).cloned().unwrap_or(Value::Null))
+            }
+            (Value::Array(items), Value::String(key)) if key == "length" => Ok(Value::from(items.len())),
+            (Value::String(text), Value::String(key)) if key == "length" => Ok(Value::from(text.chars().count())),
+            (Value::Null, _) => Err(self.error(expr, format!("Cannot read '{}' of null", to_display_string(key)))),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn binary(&self, expr: &Expr, op: BinaryOp, (left, l): (&Expr, Value), (right, r): (&Expr, Value)) -> Result<Value, RuntimeError> {
+        match op {
+            // Anything added to a string is concatenated
+            BinaryOp::Add if l.is_string() || r.is_string() => {
+                Ok(Value::String(to_display_string(&l) + &to_display_string(&r)))
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                let (a, b) = (self.number(left, &l)?, self.number(right, &r)?);
+                if matches!(op, BinaryOp::Div | BinaryOp::Rem) && b == 0.0 {
+                    return Err(self.error(expr, "Division by zero".to_string()));
+                }
+                Ok(number(match op {
+                    BinaryOp::Add => a + b,
+                    BinaryOp::Sub => a - b,
+                    BinaryOp::Mul => a * b,
+                    BinaryOp::Div => a / b,
+                    _ => a % b,
+                }))
+            }
+            BinaryOp::Eq => Ok(Value::Bool(loosely_equal(&l, &r))),
+            BinaryOp::NotEq => Ok(Value::Bool(!loosely_equal(&l, &r))),
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => {
+                let ordering = match (&l, &r) {
+                    (Value::String(a), Value::String(b)) => a.cmp(b),
+                    _ => {
+                        let (a, b) = (self.number(left, &l)?, self.number(right, &r)?);
+                        a.partial_cmp(&b).ok_or_else(|| self.error(expr, "Cannot compare NaN".to_string()))?
+                    }
+                };
+                Ok(Value::Bool(match op {
+                    BinaryOp::Lt => ordering.is_lt(),
+                    BinaryOp::LtEq => ordering.is_le(),
+                    BinaryOp::Gt => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }))
+            }
+            BinaryOp::And | BinaryOp::Or => unreachable!("short-circuited in eval"),
+        }
+    }
+
+    fn filter(&self, expr: &Expr, name: &str, value: Value, args: &[Value]) -> Result<Value, RuntimeError> {
+        match name {
+            "upper" => Ok(Value::String(to_display_string(&value).to_uppercase())),
+            "json" => Ok(Value::String(value.to_string())),
+            "date" => {
+                let format = match args.first() {
+                    Some(Value::String(format)) => format.as_str(),
+                    Some(other) => return Err(self.error(expr, format!("date format must be a string, got {}", type_name(other)))),
+                    None => "YYYY-MM-DD",
+                };
+                let date = DateTime::from_value(&value)
+                    .ok_or_else(|| self.error(expr, format!("Cannot format {} as a date", type_name(&value))))?;
+                Ok(Value::String(date.format(format)))
+            }
+            _ => Err(self.error(expr, format!("Unknown filter '{}'; expected one of upper, date, json", name))),
+        }
+    }
+
+    fn number(&self, expr: &Expr, value: &Value) -> Result<f64, RuntimeError> {
+        match value {
+            Value::Number(n) => Ok(n.as_f64().unwrap_or(0.0)),
+            Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Value::Null => Ok(0.0),
+            other => Err(self.error(expr, format!("Expected a number, got {}", type_name(other)))),
+        }
+    }
+
+    fn error(&self, expr: &Expr, message: String) -> RuntimeError {
+        error_at(&self.source, self.origin, expr.start, expr.end, message)
+    }
+}
+
+/// Text for a value rendered into the page: `null` renders nothing, and arrays and objects as JSON.
+pub fn to_display_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+/// JavaScript truthiness: `false`, `null`, `0`, `NaN` and `""` are false.
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0 && !f.is_nan()),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+fn loosely_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        // 1 == 1.0
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        Value::from(n as i64)
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// How a value's type reads in an error message, e.g. "a string".
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Builds an error for `source[start..end]`, locating it in the page through `origin`.
+fn error_at(source: &str, origin: Option<Span>, start: usize, end: usize, message: String) -> RuntimeError {
+    let span = origin.map(|origin| {
+        let advance = |(mut line, mut column): (usize, usize), text: &str| {
+            for c in text.chars() {
+                if c == '\n' {
+                    line += 1;
+                    column = 0;
+                } else {
+                    column += 1;
+                }
+            }
+            (line, column)
+        };
+        let (start_line, start_column) = advance((origin.start_line, origin.start_column), &source[..start]);
+        let (end_line, end_column) = advance((start_line, start_column), &source[start..end]);
+        Span {
+            start_line,
+            start_column,
+            end_line,
+            end_column,
+            start_offset: origin.start_offset + start,
+            end_offset: origin.start_offset + end,
+        }
+    });
+    RuntimeError::new(message, span)
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    kind: ExprKind,
+    /// Byte range in the expression source
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone)]
+enum ExprKind {
+    Literal(Value),
+    /// `[a, b]`
+    Array(Vec<Expr>),
+    /// `{ name: value, 'quoted key': value }`
+    Object(Vec<(String, Expr)>),
+    Variable(String),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    Filter(Box<Expr>, String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Binding power; higher binds tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::NotEq => 3,
+            BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    Eof,
+}
+
+impl Tok {
+    fn describe(&self) -> String {
+        match self {
+            Tok::Number(n) => format!("number {}", n),
+            Tok::Str(s) => format!("string {:?}", s),
+            Tok::Ident(name) => format!("'{}'", name),
+            Tok::Op(op) => format!("'{}'", op),
+            Tok::Eof => "end of expression".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Tok,
+    start: usize,
+    end: usize,
+}
+
+/// Longest operators first so `===` wins over `==` and `||` over `|`.
+const OPERATORS: &[&str] = &[
+    "===", "!==", "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "?", ":", "|",
+    ".", ",", "(", ")", "[", "]", "{", "}",
+];
+
+fn tokenize(source: &str, origin: Option<Span>) -> Result<Vec<Token>, RuntimeError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = rest.peek() {
+        if c.is_whitespace() {
+            rest.next();
+            continue;
+        }
+
+        let kind = if c.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(i, d)) = rest.peek() {
+                if !(d.is_ascii_digit() || (d == '.' && source[i + 1..].starts_with(|c: char| c.is_ascii_digit()))) {
+                    break;
+                }
+                end = i + d.len_utf8();
+                rest.next();
+            }
+            Tok::Number(source[start..end].parse().map_err(|_| error_at(source, origin, start, end, "Invalid number".to_string()))?)
+        } else if c == '"' || c == '\'' {
+            rest.next();
+            let mut text = String::new();
+            let mut closed = false;
+            while let Some((_, d)) = rest.next() {
+                match d {
+                    '\\' => match rest.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, escaped)) => text.push(escaped),
+                        None => break,
+                    },
+                    d if d == c => {
+                        closed = true;
+                        break;
+                    }
+                    d => text.push(d),
+                }
+            }
+            if !closed {
+                return Err(error_at(source, origin, start, source.len(), "Unterminated string".to_string()));
+            }
+            Tok::Str(text)
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let mut end = start;
+            while let Some(&(i, d)) = rest.peek() {
+                if !(d.is_alphanumeric() || d == '_' || d == '$') {
+                    break;
+                }
+                end = i + d.len_utf8();
+                rest.next();
+            }
+            Tok::Ident(source[start..end].to_string())
+        } else if let Some(op) = OPERATORS.iter().find(|op| source[start..].starts_with(**op)) {
+            for _ in 0..op.len() {
+                rest.next();
+            }
+            Tok::Op(op)
+        } else {
+            return Err(error_at(source, origin, start, start + c.len_utf8(), format!("Unexpected character '{}' in expression", c)));
+        };
+
+        let end = rest.peek().map_or(source.len(), |&(i, _)| i);
+        tokens.push(Token { kind, start, end });
+    }
+
+    tokens.push(Token { kind: Tok::Eof, start: source.len(), end: source.len() });
+    Ok(tokens)
+}
+
+struct ExprParser<'s> {
+    tokens: Vec<Token>,
+    pos: usize,
+    source: &'s str,
+    origin: Option<Span>,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != Tok::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek().kind == Tok::Op(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<Token, RuntimeError> {
+        let token = self.next();
+        if token.kind == Tok::Op(op) {
+            Ok(token)
+        } else {
+            Err(self.error(&token, format!("Expected '{}', found {}", op, token.kind.describe())))
+        }
+    }
+
+    fn error(&self, token: &Token, message: String) -> RuntimeError {
+        error_at(self.source, self.origin, token.start, token.end, message)
+    }
+
+    /// expr := conditional ('|' filter)*
+    fn parse_expr(&mut self) -> Result<Expr, RuntimeError> {
+        let mut expr = self.parse_conditional()?;
+        while self.eat("|") {
+            let token = self.next();
+            let Tok::Ident(name) = token.kind else {
+                return Err(self.error(&token, format!("Expected a filter name after '|', found {}", token.kind.describe())));
+            };
+            let mut args = Vec::new();
+            let mut end = token.end;
+            if self.eat("(") {
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.parse_conditional()?);
+                        if !self.eat(",") {
+                            break;
+                        }
+                    }
+                    end = self.expect(")")?.end;
+                } else {
+                    end = self.tokens[self.pos - 1].end;
+                }
+            }
+            let start = expr.start;
+            expr = Expr { kind: ExprKind::Filter(Box::new(expr), name, args), start, end };
+        }
+        Ok(expr)
+    }
+
+    /// conditional := binary ('?' expr ':' expr)?
+    fn parse_conditional(&mut self) -> Result<Expr, RuntimeError> {
+        let test = self.parse_binary(0)?;
+        if !self.eat("?") {
+            return Ok(test);
+        }
+        let then = self.parse_conditional()?;
+        self.expect(":")?;
+        let otherwise = self.parse_conditional()?;
+        let (start, end) = (test.start, otherwise.end);
+        Ok(Expr { kind: ExprKind::Conditional(Box::new(test), Box::new(then), Box::new(otherwise)), start, end })
+    }
+
+    /// Precedence climbing over the binary operators.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, RuntimeError> {
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.binary_op().filter(|op| op.precedence() > min_precedence) {
+            self.pos += 1;
+            let right = self.parse_binary(op.precedence())?;
+            let (start, end) = (left.start, right.end);
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), start, end };
+        }
+        Ok(left)
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        let Tok::Op(op) = self.peek().kind else { return None };
+        Some(match op {
+            "||" => BinaryOp::Or,
+            "&&" => BinaryOp::And,
+            "==" | "===" => BinaryOp::Eq,
+            "!=" | "!==" => BinaryOp::NotEq,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::LtEq,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::GtEq,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            _ => return None,
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, RuntimeError> {
+        let start = self.peek().start;
+        if self.eat("!") {
+            let operand = self.parse_unary()?;
+            let end = operand.end;
+            return Ok(Expr { kind: ExprKind::Not(Box::new(operand)), start, end });
+        }
+        if self.eat("-") {
+            let operand = self.parse_unary()?;
+            let end = operand.end;
+            return Ok(Expr { kind: ExprKind::Negate(Box::new(operand)), start, end });
+        }
+        self.parse_postfix()
+    }
+
+    /// postfix := primary ('.' name | '[' expr ']')*
+    fn parse_postfix(&mut self) -> Result<Expr, RuntimeError> {
+        let mut expr = self.parse_primary()?;
+        loop {
+            if self.eat(".") {
+                let token = self.next();
+                let Tok::Ident(name) = token.kind else {
+                    return Err(self.error(&token, format!("Expected a property name after '.', found {}", token.kind.describe())));
+                };
+                let start = expr.start;
+                expr = Expr { kind: ExprKind::Member(Box::new(expr), name), start, end: token.end };
+            } else if self.eat("[") {
+                let index = self.parse_expr()?;
+                let end = self.expect("]")?.end;
+                let start = expr.start;
+                expr = Expr { kind: ExprKind::Index(Box::new(expr), Box::new(index)), start, end };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, RuntimeError> {
+        let token = self.next();
+        let kind = match token.kind {
+            Tok::Number(n) => ExprKind::Literal(number(n)),
+            Tok::Str(ref text) => ExprKind::Literal(Value::String(text.clone())),
+            Tok::Ident(ref name) => match name.as_str() {
+                "true" => ExprKind::Literal(Value::Bool(true)),
+                "false" => ExprKind::Literal(Value::Bool(false)),
+                "null" | "undefined" => ExprKind::Literal(Value::Null),
+                _ => ExprKind::Variable(name.clone()),
+            },
+            Tok::Op("(") => {
+                let mut inner = self.parse_expr()?;
+                let end = self.expect(")")?.end;
+                inner.start = token.start;
+                inner.end = end;
+                return Ok(inner);
+            }
+            Tok::Op("[") => {
+                let mut items = Vec::new();
+                while !self.eat("]") {
+                    items.push(self.parse_conditional()?);
+                    if !self.eat(",") {
+                        self.expect("]")?;
+                        break;
+                    }
+                }
+                ExprKind::Array(items)
+            }
+            Tok::Op("{") => {
+                let mut entries = Vec::new();
+                while !self.eat("}") {
+                    let key = self.next();
+                    let (Tok::Ident(name) | Tok::Str(name)) = key.kind else {
+                        return Err(self.error(&key, format!("Expected a property name, found {}", key.kind.describe())));
+                    };
+                    self.expect(":")?;
+                    entries.push((name, self.parse_conditional()?));
+                    if !self.eat(",") {
+                        self.expect("}")?;
+                        break;
+                    }
+                }
+                ExprKind::Object(entries)
+            }
+            Tok::Eof => return Err(self.error(&token, "Expected an expression".to_string())),
+            ref other => return Err(self.error(&token, format!("Unexpected {} in expression", other.describe()))),
+        };
+        let end = match kind {
+            ExprKind::Array(_) | ExprKind::Object(_) => self.tokens[self.pos - 1].end,
+            _ => token.end,
+        };
+        Ok(Expr { kind, start: token.start, end })
+    }
+}
+
+/// Just enough date handling for the `date` filter: ISO 8601 strings and Unix timestamps in milliseconds.
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl DateTime {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(Self::from_unix_millis(n.as_f64()? as i64)),
+            Value::String(text) => Self::parse_iso(text),
+            _ => None,
+        }
+    }
+
+    fn from_unix_millis(millis: i64) -> Self {
+        let seconds = millis.div_euclid(1000);
+        let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+        // Civil-from-days, after Howard Hinnant's date algorithms
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (time / 3600) as u32,
+            minute: (time % 3600 / 60) as u32,
+            second: (time % 60) as u32,
+        }
+    }
+
+    /// `2024-03-01`, optionally followed by `T` or a space and `HH:MM[:SS]`; any zone suffix is ignored.
+    fn parse_iso(text: &str) -> Option<Self> {
+        let field = |range: std::ops::Range<usize>| text.get(range)?.parse::<u32>().ok();
+        let year = text.get(0..4)?.parse::<i64>().ok()?;
+        if text.get(4..5)? != "-" || text.get(7..8)? != "-" {
+            return None;
+        }
+        let (month, day) = (field(5..7)?, field(8..10)?);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+
+        let (mut hour, mut minute, mut second) = (0, 0, 0);
+        if matches!(text.get(10..11), Some("T" | " ")) {
+            hour = field(11..13)?;
+            minute = field(14..16)?;
+            second = field(17..19).unwrap_or(0);
+        }
+        Some(Self { year, month, day, hour, minute, second })
+    }
+
+    /// Formats with `YYYY`, `MM`, `DD`, `HH`, `mm` and `ss` placeholders.
+    fn format(&self, format: &str) -> String {
+        let mut out = String::new();
+        let mut rest = format;
+        while !rest.is_empty() {
+            let replacements: [(&str, String); 6] = [
+                ("YYYY", format!("{:04}", self.year)),
+                ("MM", format!("{:02}", self.month)),
+                ("DD", format!("{:02}", self.day)),
+                ("HH", format!("{:02}", self.hour)),
+                ("mm", format!("{:02}", self.minute)),
+                ("ss", format!("{:02}", self.second)),
+            ];
+            match replacements.iter().find(|(token, _)| rest.starts_with(token)) {
+                Some((token, value)) => {
+                    out.push_str(value);
+                    rest = &rest[token.len()..];
+                }
+                None => {
+                    let c = rest.chars().next().expect("rest is not empty");
+                    out.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Shorthand for evaluating a one-off expression against `data`.
+pub fn evaluate(source: &str, origin: Option<Span>, data: &Value) -> Result<Value, RuntimeError> {
+    Expression::parse(source, origin)?.evaluate(&Scope::new(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(source: &str, data: Value) -> Result<Value, RuntimeError> {
+        evaluate(source, None, &data)
+    }
+
+    #[test]
+    fn test_operators_and_precedence() {
+        let data = json!({ "a": 2, "b": 3, "user": { "name": "Ada", "tags": ["x", "y"] } });
+
+        assert_eq!(eval("a + b * 2", data.clone()).unwrap(), json!(8));
+        assert_eq!(eval("(a + b) * 2 - 1", data.clone()).unwrap(), json!(9));
+        assert_eq!(eval("7 / 2", data.clone()).unwrap(), json!(3.5));
+        assert_eq!(eval("a < b && !(a == b)", data.clone()).unwrap(), json!(true));
+        assert_eq!(eval("user.missing || 'none'", data.clone()).unwrap(), json!("none"));
+        assert_eq!(eval("a > b ? 'big' : a == 2 ? 'two' : 'small'", data.clone()).unwrap(), json!("two"));
+        assert_eq!(eval("'Hi ' + user.name + '!' + a", data.clone()).unwrap(), json!("Hi Ada!2"));
+        assert_eq!(eval("user.tags[1] + user.tags.length", data.clone()).unwrap(), json!("y2"));
+        assert_eq!(eval("[]", data.clone()).unwrap(), json!([]));
+        assert_eq!(eval("[a, b * 2,]", data.clone()).unwrap(), json!([2, 6]));
+        assert_eq!(eval("{ name: user.name, 'n': [a], }.n[0]", data).unwrap(), json!(2));
+    }
+
+    #[test]
+    fn test_filters() {
+        let data = json!({ "name": "ada", "when": "2024-03-01T09:05:00Z", "stamp": 0, "list": [1, "a"] });
+
+        assert_eq!(eval("name | upper", data.clone()).unwrap(), json!("ADA"));
+        assert_eq!(eval("'x' + name | upper", data.clone()).unwrap(), json!("XADA"));
+        assert_eq!(eval("when | date", data.clone()).unwrap(), json!("2024-03-01"));
+        assert_eq!(eval("when | date('DD/MM/YYYY HH:mm')", data.clone()).unwrap(), json!("01/03/2024 09:05"));
+        assert_eq!(eval("stamp | date('YYYY-MM-DD HH:mm:ss')", data.clone()).unwrap(), json!("1970-01-01 00:00:00"));
+        assert_eq!(eval("list | json", data).unwrap(), json!(r#"[1,"a"]"#));
+    }
+
+    #[test]
+    fn test_errors_point_into_the_page() {
+        // `<p>{user.name | shout}</p>`, with the expression on line 3
+        let origin = Span { start_line: 3, start_column: 4, start_offset: 21, end_line: 3, end_column: 23, end_offset: 40 };
+        let err = evaluate("user.name | shout", Some(origin), &json!({ "user": { "name": "Ada" } })).unwrap_err();
+
+        assert!(err.message.contains("Unknown filter 'shout'"));
+        let span = err.span.unwrap();
+        assert_eq!((span.start_line, span.start_column, span.end_column), (3, 4, 21));
+        assert_eq!((span.start_offset, span.end_offset), (21, 38));
+
+        let err = evaluate("usr.name", Some(origin), &json!({})).unwrap_err();
+        assert_eq!(err.message, "Unknown variable 'usr'");
+        assert_eq!(err.span.unwrap().end_column, 7);
+
+        let err = eval("a +", json!({ "a": 1 })).unwrap_err();
+        assert_eq!(err.message, "Expected an expression");
+        assert!(eval("1 / 0", json!({})).is_err());
+        assert!(eval("'open", json!({})).is_err());
+    }
+}
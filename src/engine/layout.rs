@@ -0,0 +1,78 @@
+// Layouts: `_layout.dts` files wrapping every page in their directory and below
+
+use crate::error::DesignTimeError;
+use crate::{Lexer, Parser};
+use designtime_ast::{Node, SLOT_ELEMENT};
+
+/// File name of a directory's layout.
+pub const LAYOUT_FILE: &str = "_layout.dts";
+
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Source file, which errors in the layout are reported against
+    pub path: String,
+    /// The nodes wrapping the page, which goes in at the `<slot />`
+    pub body: Vec<Node>,
+}
+
+impl Layout {
+    /// Parses a layout's source, which needs a `<slot />` for the page to go in.
+    pub fn parse(path: &str, source: &str) -> Result<Self, Vec<DesignTimeError>> {
+        let (body, errors) = Parser::new(Lexer::new(source)).parse_document_recovering();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        if !has_slot(&body) {
+            return Err(vec![DesignTimeError::CompilerError(format!("Layout {} has no <slot /> for the page", path))]);
+        }
+        Ok(Self { path: path.to_string(), body })
+    }
+}
+
+fn has_slot(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        Node::Element { tag_name, .. } if tag_name == SLOT_ELEMENT => true,
+        Node::Element { children, .. } | Node::Component { children, .. } | Node::Fragment(children) => has_slot(children),
+        Node::If { branches, otherwise, .. } => {
+            branches.iter().any(|branch| has_slot(&branch.children)) || otherwise.as_deref().is_some_and(has_slot)
+        }
+        Node::Each { children, otherwise, .. } => has_slot(children) || otherwise.as_deref().is_some_and(has_slot),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::WorkspaceConfig;
+    use crate::Runtime;
+    use dominate::dom::DomNode;
+
+    fn dom_text(nodes: &[DomNode]) -> String {
+        nodes
+            .iter()
+            .map(|node| match node {
+                DomNode::Element { tag, children, .. } => format!("<{}>{}</{}>", tag, dom_text(children), tag),
+                DomNode::Text(text) | DomNode::Expression(text) => text.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_layouts() {
+        let root = Layout::parse("_layout.dts", "<body><nav>{site}</nav><slot /></body>").unwrap();
+        let blog = Layout::parse("blog/_layout.dts", r#"<main><slot name="title" /><slot /></main>"#).unwrap();
+        assert!(Layout::parse("bad/_layout.dts", "<main></main>").is_err());
+
+        let config: WorkspaceConfig = serde_json::from_value(serde_json::json!({ "project": { "name": "test", "version": "1.0.0" } })).unwrap();
+        let mut runtime = Runtime::new(config);
+        let page = Parser::new(Lexer::new(r#"<h1 slot="title">{params.slug}</h1><p>Post</p>"#)).parse_document().unwrap();
+        let data = serde_json::json!({ "site": "Blog", "params": { "slug": "hello" } });
+        let page = runtime.process_page(page, &[&root, &blog], &data).unwrap();
+        assert_eq!(dom_text(&page.nodes), "<body><nav>Blog</nav><main><h1>hello</h1><p>Post</p></main></body>");
+
+        let broken = Layout::parse("blog/_layout.dts", "<main>{missing.title}<slot /></main>").unwrap();
+        let err = runtime.process_page(Vec::new(), &[&broken], &data).unwrap_err();
+        assert_eq!(err.file.as_deref(), Some("blog/_layout.dts"));
+    }
+}
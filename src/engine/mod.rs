@@ -1,5 +1,11 @@
 pub mod workspace;
 pub mod runtime;
+pub mod expression;
+pub mod template;
+pub mod components;
+pub mod router;
+pub mod layout;
+pub mod build;
 
 pub use workspace::*;
 pub use runtime::*;
@@ -0,0 +1,428 @@
+// File-system routing: every `*.page.dts` under `routes.pagesDir` is a page served at the URL of its path
+
+use crate::engine::layout::{Layout, LAYOUT_FILE};
+use crate::error::{DesignTimeError, FileError};
+use crate::workspace::{Routes, WorkspaceConfig};
+use glob::Pattern;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Where pages live when designtime.json doesn't set `routes.pagesDir`.
+pub const DEFAULT_PAGES_DIR: &str = "src/pages";
+
+/// Suffix marking a `.dts` file as a page rather than a component.
+pub const PAGE_SUFFIX: &str = ".page.dts";
+
+/// Key of a page's data holding the parameters of its route, as in `{params.slug}`.
+pub const PARAMS_KEY: &str = "params";
+
+/// One `/`-separated part of a route.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Static(String),
+    /// `[slug]`, matching any one part of the URL
+    Param(String),
+    /// `[...rest]`, matching the rest of the URL, one part or more
+    Rest(String),
+}
+
+impl Segment {
+    fn parse(segment: &str) -> Self {
+        match segment.strip_prefix('[').and_then(|name| name.strip_suffix(']')) {
+            Some(name) => match name.strip_prefix("...") {
+                Some(name) => Segment::Rest(name.to_string()),
+                None => Segment::Param(name.to_string()),
+            },
+            None => Segment::Static(segment.to_string()),
+        }
+    }
+
+    /// How specific the segment is: static beats `[param]`, which beats `[...rest]`.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 2,
+            Segment::Param(_) => 1,
+            Segment::Rest(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    /// The URL the page is served at, e.g. `/blog/hello` or `/blog/[slug]`
+    pub path: String,
+    /// The page's source file
+    pub file: PathBuf,
+    pub segments: Vec<Segment>,
+    /// The `_layout.dts` files wrapping the page, outermost first
+    pub layouts: Vec<PathBuf>,
+}
+
+impl Route {
+    pub fn new(path: &str, file: PathBuf) -> Self {
+        let segments = path.split('/').filter(|part| !part.is_empty()).map(Segment::parse).collect();
+        Self { path: path.to_string(), file, segments, layouts: Vec::new() }
+    }
+
+    /// The parameters of `path` if this route serves it.
+    pub fn matches(&self, path: &str) -> Option<Map<String, Value>> {
+        let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+        let mut params = Map::new();
+        let mut i = 0;
+        for segment in &self.segments {
+            match segment {
+                Segment::Static(name) => {
+                    if parts.get(i) != Some(&name.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.insert(name.clone(), Value::String(parts.get(i)?.to_string()));
+                }
+                Segment::Rest(name) => {
+                    if i >= parts.len() {
+                        return None;
+                    }
+                    params.insert(name.clone(), Value::String(parts[i..].join("/")));
+                    i = parts.len();
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        (i == parts.len()).then_some(params)
+    }
+
+    /// The URLs this route serves, with the parameter names left out, so `/blog/[slug]`
+    /// and `/blog/[id]` are the same.
+    fn shape(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Static(name) => name.as_str(),
+                Segment::Param(_) => "[]",
+                Segment::Rest(_) => "[...]",
+            })
+            .collect()
+    }
+}
+
+/// A route serving a URL, with the values of its parameters.
+#[derive(Debug, Clone)]
+pub struct RouteMatch<'a> {
+    pub route: &'a Route,
+    pub params: Map<String, Value>,
+}
+
+impl RouteMatch<'_> {
+    /// `data` with the route's parameters added as `params`, to be passed to `Runtime::process_nodes`.
+    pub fn data(&self, data: &Value) -> Value {
+        let mut data = match data {
+            Value::Null => Value::Object(Map::new()),
+            data => data.clone(),
+        };
+        if let Value::Object(map) = &mut data {
+            map.insert(PARAMS_KEY.to_string(), Value::Object(self.params.clone()));
+        }
+        data
+    }
+}
+
+/// The pages of a workspace by URL, plus the page shown when no route matches and the layouts
+/// wrapping them.
+#[derive(Debug, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    not_found: Option<Route>,
+    layouts: HashMap<PathBuf, Layout>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the routes from the workspace's `routes` settings, with paths relative to `root`.
+    ///
+    /// Unless auto-registration is turned off, every `*.page.dts` file under `pagesDir` becomes a route:
+    /// `about.page.dts` is `/about`, `blog/hello.page.dts` is `/blog/hello` and an `index.page.dts`
+    /// is served at its directory, so `index.page.dts` is `/` and `blog/index.page.dts` is `/blog`.
+    /// Files and directories named `[param]` or `[...rest]` are dynamic (see `Segment`).
+    /// Files matching one of the `exclude` globs are skipped; globs are matched against the path
+    /// from `root` as well as the path inside `pagesDir`. `notFoundPage` is the 404 page and isn't
+    /// given a route of its own.
+    ///
+    /// A `_layout.dts` wraps every page in its directory and below, the 404 page included when it
+    /// lives in `pagesDir`; a page in `blog/` is wrapped in `_layout.dts`, then `blog/_layout.dts`.
+    pub fn load(config: &WorkspaceConfig, root: &Path) -> Result<Self, Vec<FileError>> {
+        let mut router = Self::new();
+        let defaults = Routes::default();
+        let routes = config.routes.as_ref().unwrap_or(&defaults);
+
+        let mut errors = Vec::new();
+        let mut exclude = Vec::new();
+        for (i, glob) in routes.exclude.iter().flatten().enumerate() {
+            match Pattern::new(glob) {
+                Ok(pattern) => exclude.push(pattern),
+                Err(err) => errors.push(config_error(&format!("/routes/exclude/{}", i), format!("Invalid glob '{}': {}", glob, err))),
+            }
+        }
+
+        let not_found = routes.not_found_page.as_ref().map(|page| root.join(page));
+        if let Some(page) = &not_found {
+            if page.is_file() {
+                router.set_not_found(page.clone());
+            } else {
+                let message = format!("The not found page '{}' does not exist", page.display());
+                errors.push(config_error("/routes/notFoundPage", message));
+            }
+        }
+
+        // Compared canonicalised, so `./src/pages/404.page.dts` still matches the walked file
+        let not_found_file = not_found.as_ref().and_then(|page| page.canonicalize().ok());
+        let pages_dir = routes.pages_dir.as_deref().unwrap_or(DEFAULT_PAGES_DIR);
+        let directory = root.join(pages_dir);
+        if routes.auto_register != Some(false) && directory.is_dir() {
+            for entry in WalkDir::new(&directory).sort_by_file_name() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        errors.push(FileError {
+                            path: directory.display().to_string(),
+                            source: String::new(),
+                            error: DesignTimeError::Unknown(err.to_string()),
+                        });
+                        continue;
+                    }
+                };
+                let file = entry.path();
+                let Ok(relative) = file.strip_prefix(&directory) else {
+                    continue;
+                };
+                if !entry.file_type().is_file() || not_found_file.as_ref().is_some_and(|page| file.canonicalize().is_ok_and(|file| file == *page)) {
+                    continue;
+                }
+                if entry.file_name() == LAYOUT_FILE {
+                    match std::fs::read_to_string(file) {
+                        Ok(source) => match Layout::parse(&file.display().to_string(), &source) {
+                            Ok(layout) => {
+                                router.layouts.insert(file.to_path_buf(), layout);
+                            }
+                            Err(parse_errors) => errors.extend(parse_errors.into_iter().map(|error| FileError {
+                                path: file.display().to_string(),
+                                source: source.clone(),
+                                error,
+                            })),
+                        },
+                        Err(err) => errors.push(FileError { path: file.display().to_string(), source: String::new(), error: err.into() }),
+                    }
+                    continue;
+                }
+                let Some(path) = route_path(relative) else {
+                    continue;
+                };
+                let in_workspace = Path::new(pages_dir).join(relative);
+                if exclude.iter().any(|pattern| pattern.matches_path(relative) || pattern.matches_path(&in_workspace)) {
+                    continue;
+                }
+                let route = Route::new(&path, file.to_path_buf());
+                if let Err(error) = router.add(route) {
+                    errors.push(FileError { path: file.display().to_string(), source: String::new(), error });
+                }
+            }
+        }
+
+        let layouts: Vec<&PathBuf> = router.layouts.keys().collect();
+        let chains: Vec<Vec<PathBuf>> = router
+            .routes
+            .iter()
+            .chain(router.not_found.as_ref())
+            .map(|route| layout_chain(&route.file, &directory, &layouts))
+            .collect();
+        for (route, chain) in router.routes.iter_mut().chain(router.not_found.as_mut()).zip(chains) {
+            route.layouts = chain;
+        }
+
+        if errors.is_empty() { Ok(router) } else { Err(errors) }
+    }
+
+    /// Adds a route, failing if another page already serves the same URLs or if a `[...rest]`
+    /// segment isn't the last one.
+    pub fn add(&mut self, route: Route) -> Result<(), DesignTimeError> {
+        if let Some(position) = route.segments.iter().position(|segment| matches!(segment, Segment::Rest(_)))
+            && position + 1 < route.segments.len()
+        {
+            return Err(DesignTimeError::CompilerError(format!(
+                "Route '{}' has a [...rest] segment that isn't last in {}",
+                route.path,
+                route.file.display()
+            )));
+        }
+        if let Some(existing) = self.routes.iter().find(|existing| existing.shape() == route.shape()) {
+            return Err(DesignTimeError::CompilerError(format!(
+                "Route '{}' is claimed by both {} and {}",
+                route.path,
+                existing.file.display(),
+                route.file.display()
+            )));
+        }
+        self.routes.push(route);
+        Ok(())
+    }
+
+    /// Sets the page shown for URLs without a route.
+    pub fn set_not_found(&mut self, file: PathBuf) {
+        self.not_found = Some(Route::new("/404", file));
+    }
+
+    pub fn routes(&self) -> &[Route] {
+        &self.routes
+    }
+
+    pub fn not_found(&self) -> Option<&Route> {
+        self.not_found.as_ref()
+    }
+
+    /// The layouts wrapping `route`, outermost first, to be passed to `Runtime::process_page`.
+    pub fn layouts(&self, route: &Route) -> Vec<&Layout> {
+        route.layouts.iter().filter_map(|path| self.layouts.get(path)).collect()
+    }
+
+    /// The route serving `url`, ignoring a trailing `/`, a query string and a fragment.
+    /// When several routes match, the most specific wins, compared segment by segment:
+    /// `/blog/new` is chosen over `/blog/[slug]`, which is chosen over `/blog/[...rest]`.
+    pub fn find(&self, url: &str) -> Option<RouteMatch<'_>> {
+        let path = normalize(url);
+        self.routes
+            .iter()
+            .filter_map(|route| route.matches(path).map(|params| RouteMatch { route, params }))
+            .max_by_key(|found| found.route.segments.iter().map(Segment::rank).collect::<Vec<_>>())
+    }
+
+    /// Like `find`, but falls back to the 404 page.
+    pub fn resolve(&self, url: &str) -> Option<RouteMatch<'_>> {
+        self.find(url).or_else(|| self.not_found.as_ref().map(|route| RouteMatch { route, params: Map::new() }))
+    }
+}
+
+/// The URL of a page from its path inside the pages directory, or `None` if it isn't a page.
+pub fn route_path(relative: &Path) -> Option<String> {
+    let file_name = relative.file_name()?.to_str()?;
+    let name = file_name.strip_suffix(PAGE_SUFFIX)?;
+
+    let mut segments: Vec<String> = relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if name != "index" {
+        segments.push(name.to_string());
+    }
+    Some(format!("/{}", segments.join("/")))
+}
+
+/// The layouts among `layouts` for a page at `file`, from `pages_dir` down to the page's directory.
+fn layout_chain(file: &Path, pages_dir: &Path, layouts: &[&PathBuf]) -> Vec<PathBuf> {
+    let mut chain: Vec<PathBuf> = file
+        .ancestors()
+        .skip(1)
+        .take_while(|directory| directory.starts_with(pages_dir))
+        .map(|directory| directory.join(LAYOUT_FILE))
+        .filter(|layout| layouts.contains(&layout))
+        .collect();
+    chain.reverse();
+    chain
+}
+
+fn normalize(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+fn config_error(path: &str, message: String) -> FileError {
+    FileError {
+        path: "designtime.json".to_string(),
+        source: String::new(),
+        error: DesignTimeError::ConfigError { path: path.to_string(), message },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_paths() {
+        assert_eq!(route_path(Path::new("index.page.dts")).as_deref(), Some("/"));
+        assert_eq!(route_path(Path::new("about.page.dts")).as_deref(), Some("/about"));
+        assert_eq!(route_path(Path::new("blog/index.page.dts")).as_deref(), Some("/blog"));
+        assert_eq!(route_path(Path::new("blog/2024/hello.page.dts")).as_deref(), Some("/blog/2024/hello"));
+        assert_eq!(route_path(Path::new("Card.dts")), None);
+    }
+
+    #[test]
+    fn test_load_routes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in ["src/pages/index.page.dts", "src/pages/blog/index.page.dts", "src/pages/blog/hello.page.dts",
+            "src/pages/admin/users.page.dts", "src/pages/404.page.dts", "src/pages/Nav.dts", "src/pages/_layout.dts",
+            "src/pages/blog/_layout.dts"]
+        {
+            let file = root.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, "<main><slot /></main>").unwrap();
+        }
+
+        let config: WorkspaceConfig = serde_json::from_value(serde_json::json!({
+            "project": { "name": "test", "version": "1.0.0" },
+            "routes": { "pagesDir": "src/pages", "exclude": ["**/admin/**"], "notFoundPage": "src/pages/../pages/404.page.dts" }
+        }))
+        .unwrap();
+        let router = Router::load(&config, root).unwrap();
+
+        let paths: Vec<&str> = router.routes().iter().map(|route| route.path.as_str()).collect();
+        assert_eq!(paths, ["/blog/hello", "/blog", "/"]);
+        assert_eq!(router.find("/blog/?page=2").unwrap().route.file, root.join("src/pages/blog/index.page.dts"));
+        assert!(router.find("/admin/users").is_none());
+        assert!(router.resolve("/missing").unwrap().route.file.ends_with("404.page.dts"));
+
+        let layouts = |url| router.layouts(router.resolve(url).unwrap().route).iter().map(|layout| layout.path.clone()).collect::<Vec<_>>();
+        let layout = |file| root.join(file).display().to_string();
+        assert_eq!(layouts("/blog/hello"), [layout("src/pages/_layout.dts"), layout("src/pages/blog/_layout.dts")]);
+        assert_eq!(layouts("/"), [layout("src/pages/_layout.dts")]);
+        assert_eq!(layouts("/missing"), [layout("src/pages/_layout.dts")]);
+
+        // Without a `routes` section every page under the default pages directory is routed
+        let config: WorkspaceConfig = serde_json::from_value(serde_json::json!({ "project": { "name": "test", "version": "1.0.0" } })).unwrap();
+        let router = Router::load(&config, root).unwrap();
+        assert_eq!(router.routes().len(), 5);
+        assert!(router.find("/admin/users").is_some());
+        assert!(router.not_found().is_none());
+    }
+
+    #[test]
+    fn test_dynamic_routes() {
+        let mut router = Router::new();
+        for path in ["/blog/new", "/blog/[slug]", "/docs/[...rest]", "/[lang]/about"] {
+            router.add(Route::new(path, PathBuf::from(format!("{}.page.dts", path)))).unwrap();
+        }
+
+        let found = router.find("/blog/hello").unwrap();
+        assert_eq!(found.route.path, "/blog/[slug]");
+        assert_eq!(found.data(&serde_json::json!({ "title": "Hi" })), serde_json::json!({ "title": "Hi", "params": { "slug": "hello" } }));
+        assert_eq!(router.find("/blog/new").unwrap().route.path, "/blog/new");
+        assert_eq!(router.find("/docs/guide/intro").unwrap().params["rest"], "guide/intro");
+        assert!(router.find("/docs").is_none());
+        assert_eq!(router.find("/fr/about").unwrap().params["lang"], "fr");
+
+        let err = router.add(Route::new("/blog/[id]", PathBuf::from("blog/[id].page.dts"))).unwrap_err();
+        assert_eq!(err.message(), "Route '/blog/[id]' is claimed by both /blog/[slug].page.dts and blog/[id].page.dts");
+        assert!(router.add(Route::new("/[...rest]/edit", PathBuf::from("edit.page.dts"))).is_err());
+    }
+}
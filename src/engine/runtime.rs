@@ -1,6 +1,12 @@
 use designtime_ast::Node;
+use crate::engine::components::ComponentRegistry;
+use crate::engine::expression::Scope;
+use crate::engine::layout::Layout;
+use crate::engine::template::evaluate_nodes;
 use crate::workspace::WorkspaceConfig;
 use dominate::prelude::*;
+use dominate::transform::fill_slots;
+use serde_json::Value;
 use std::fmt;
 
 #[derive(Debug)]
@@ -8,6 +14,8 @@ pub struct RuntimeError {
     pub message: String,
     pub source: Option<Box<dyn std::error::Error + Send + Sync>>,
     pub span: Option<crate::error::Span>,
+    /// The file `span` points into, when it isn't the page being rendered, e.g. a component
+    pub file: Option<String>,
 }
 
 impl fmt::Display for RuntimeError {
@@ -24,6 +32,18 @@ impl fmt::Display for RuntimeError {
     }
 }
 
+impl RuntimeError {
+    pub fn new(message: impl Into<String>, span: Option<crate::error::Span>) -> Self {
+        Self { message: message.into(), source: None, span, file: None }
+    }
+
+    /// Marks the error as coming from `file`, unless a nested file was already recorded.
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.file.get_or_insert_with(|| file.to_string());
+        self
+    }
+}
+
 impl std::error::Error for RuntimeError {}
 
 impl Clone for RuntimeError {
@@ -35,6 +55,7 @@ impl Clone for RuntimeError {
                 Box::new(std::io::Error::new(std::io::ErrorKind::Other, msg)) as Box<dyn std::error::Error + Send + Sync>
             }),
             span: self.span,
+            file: self.file.clone(),
         }
     }
     
@@ -45,6 +66,7 @@ impl Clone for RuntimeError {
 
 pub struct Runtime {
     pub workspace: WorkspaceConfig,
+    pub components: ComponentRegistry,
     pub last_error: Option<RuntimeError>,
 }
 
@@ -52,12 +74,22 @@ impl Runtime {
     pub fn new(workspace: WorkspaceConfig) -> Self {
         Self {
             workspace,
+            components: ComponentRegistry::new(),
             last_error: None,
         }
     }
 
-    /// Accepts parsed AST nodes, transforms them into DomNodes, and retrieves CSS.
-    pub fn process_nodes(&mut self, nodes: Vec<Node>) -> Result<(Vec<DomNode>, String), RuntimeError> {
+    /// Use `components` for the capitalised tags in pages, e.g. from `ComponentRegistry::load`.
+    pub fn with_components(mut self, components: ComponentRegistry) -> Self {
+        self.components = components;
+        self
+    }
+
+    /// Accepts parsed AST nodes, evaluates their expressions against `data`,
+    /// transforms them into DomNodes, and retrieves CSS.
+    pub fn process_nodes(&mut self, nodes: Vec<Node>, data: &Value) -> Result<(Vec<DomNode>, String), RuntimeError> {
+        let nodes = self.evaluate_page(nodes, &[], data)?;
+
         // Call dominate's transform
         let dom_nodes: Vec<DomNode> = transform(nodes);
 
@@ -67,13 +99,34 @@ impl Runtime {
         Ok((dom_nodes, css))
     }
 
-    pub fn run(&mut self, nodes: Vec<Node>) {
-        match self.process_nodes(nodes) {
-            Ok((dom_nodes, css)) => {
-                println!("DomNodes:\n{:#?}", dom_nodes);
-                println!("CSS:\n{}", css);
+    /// Like `process_nodes`, but first wraps the page in `layouts`, outermost first: each layout is
+    /// evaluated against the same `data` and the page, or the layout inside it, fills its `<slot />`s.
+    /// The page's CSS and scripts come back with its DomNodes.
+    pub fn process_page(&mut self, nodes: Vec<Node>, layouts: &[&Layout], data: &Value) -> Result<Page, RuntimeError> {
+        Ok(transform_page(self.evaluate_page(nodes, layouts, data)?))
+    }
+
+    fn evaluate_page(&self, nodes: Vec<Node>, layouts: &[&Layout], data: &Value) -> Result<Vec<Node>, RuntimeError> {
+        let scope = Scope::new(data);
+        let mut nodes = evaluate_nodes(nodes, &scope, &self.components)?;
+        for layout in layouts.iter().rev() {
+            let body = evaluate_nodes(layout.body.clone(), &scope, &self.components).map_err(|err| err.in_file(&layout.path))?;
+            nodes = fill_slots(body, nodes).map_err(|err| RuntimeError::new(format!("Layout {} {}", layout.path, err), err.span))?;
+        }
+        Ok(nodes)
+    }
+
+    pub fn run(&mut self, nodes: Vec<Node>, layouts: &[&Layout], data: &Value) {
+        match self.process_page(nodes, layouts, data) {
+            Ok(page) => {
+                println!("DomNodes:\n{:#?}", page.nodes);
+                println!("CSS:\n{}", page.styles.generate_css());
+                for script in page.scripts {
+                    println!("Script:\n{}", script);
+                }
             }
             Err(e) => {
+                self.last_error = Some(e.clone());
                 eprintln!("Runtime error: {}", e);
                 if let Some(source) = &e.source {
                     eprintln!("Caused by: {}", source);
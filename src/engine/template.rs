@@ -0,0 +1,422 @@
+// Evaluates the dynamic parts of a page against its data before it becomes a DOM
+
+use crate::engine::components::{Component, ComponentRegistry};
+use crate::engine::expression::{to_display_string, truthy, type_name, Expression, Scope};
+use crate::engine::runtime::RuntimeError;
+use dominate::transform::{check_slots, flatten_nodes};
+use designtime_ast::{Attribute, AttributePart, AttributeValue, ClassName, Node, Span};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// Replaces every expression in `nodes` with its value: text expressions become text,
+/// interpolated attributes become literal ones, and blocks become the fragment they render.
+/// Elements rendered by a keyed `{#each}` get their item's key, and components from `components`
+/// are expanded into the nodes they render.
+pub fn evaluate_nodes(nodes: Vec<Node>, scope: &Scope, components: &ComponentRegistry) -> Result<Vec<Node>, RuntimeError> {
+    evaluate_all(nodes, scope, components, &[])
+}
+
+/// `expanding` holds the files of the components being expanded around `nodes`, so a component
+/// that ends up using itself is an error instead of endless recursion.
+fn evaluate_all<'a>(nodes: Vec<Node>, scope: &Scope, components: &'a ComponentRegistry, expanding: &[&'a str]) -> Result<Vec<Node>, RuntimeError> {
+    nodes.into_iter().map(|node| evaluate_node(node, scope, components, expanding)).collect()
+}
+
+fn evaluate_node<'a>(node: Node, scope: &Scope, components: &'a ComponentRegistry, expanding: &[&'a str]) -> Result<Node, RuntimeError> {
+    Ok(match node {
+        Node::Expression { expression, span } => {
+            let value = Expression::parse(&expression, span.map(Span::inner))?.evaluate(scope)?;
+            Node::Text { content: to_display_string(&value), span }
+        }
+        Node::Element { tag_name, attributes, mut class_names, children, key, span } => {
+            let attributes = evaluate_attributes(attributes, scope)?;
+            if let Some(class) = attributes.iter().find(|attr| attr.name == "class")
+                && class_names.is_empty()
+                && let Some(value) = class.value.as_str()
+            {
+                class_names = value.split_whitespace().map(ClassName::new).collect();
+            }
+            Node::Element {
+                tag_name,
+                attributes,
+                class_names,
+                children: evaluate_all(children, scope, components, expanding)?,
+                key,
+                span,
+            }
+        }
+        Node::RawText { tag_name, attributes, content, span } => Node::RawText {
+            tag_name,
+            attributes: evaluate_attributes(attributes, scope)?,
+            content,
+            span,
+        },
+        Node::Fragment(children) => Node::Fragment(evaluate_all(children, scope, components, expanding)?),
+        // The content passed in is evaluated here, in the caller's scope; `dominate::transform`
+        // later puts it into the body's slots
+        Node::Component { name, attributes, children, span, .. } => {
+            let Some(component) = components.resolve(&name) else {
+                return Err(RuntimeError::new(format!("Unknown component <{}>", name), span));
+            };
+            if expanding.contains(&component.path.as_str()) {
+                return Err(RuntimeError::new(format!("<{}> uses itself", name), span));
+            }
+            let props = Value::Object(evaluate_props(component, &attributes, scope, span)?);
+            let inner: Vec<&str> = expanding.iter().copied().chain([component.path.as_str()]).collect();
+            let mut body = evaluate_all(component.body.clone(), &Scope::new(&props), components, &inner)
+                .map_err(|err| err.in_file(&component.path))?;
+            if let Some(scope) = &component.scope {
+                body = add_scope_attribute(body, scope);
+            }
+            let children = evaluate_all(children, scope, components, expanding)?;
+            check_slots(&body, &children).map_err(|err| RuntimeError::new(format!("<{}> {}", name, err), err.span))?;
+            Node::Component { name, attributes, children, body: Some(body), span }
+        }
+        Node::If { branches, otherwise, .. } => {
+            for branch in branches {
+                if truthy(&Expression::parse(&branch.condition, branch.condition_span)?.evaluate(scope)?) {
+                    return Ok(Node::Fragment(evaluate_all(branch.children, scope, components, expanding)?));
+                }
+            }
+            Node::Fragment(evaluate_all(otherwise.unwrap_or_default(), scope, components, expanding)?)
+        }
+        Node::Each { items, items_span, item, index, key, key_span, children, otherwise, .. } => {
+            let list = match Expression::parse(&items, items_span)?.evaluate(scope)? {
+                Value::Array(list) => list,
+                Value::Null => Vec::new(),
+                other => {
+                    return Err(RuntimeError::new(
+                        format!("{{#each}} needs an array, but '{}' is {}", items, type_name(&other)),
+                        items_span,
+                    ))
+                }
+            };
+            if list.is_empty() {
+                return Ok(Node::Fragment(evaluate_all(otherwise.unwrap_or_default(), scope, components, expanding)?));
+            }
+
+            let key = key.map(|key| Expression::parse(&key, key_span)).transpose()?;
+            let mut seen_keys = HashSet::new();
+            let mut rendered = Vec::with_capacity(list.len());
+            for (i, value) in list.into_iter().enumerate() {
+                let mut item_scope = scope.child();
+                item_scope.set(&item, value);
+                if let Some(index) = &index {
+                    item_scope.set(index, Value::from(i));
+                }
+
+                let mut nodes = evaluate_all(children.clone(), &item_scope, components, expanding)?;
+                if let Some(key) = &key {
+                    let item_key = to_display_string(&key.evaluate(&item_scope)?);
+                    if !seen_keys.insert(item_key.clone()) {
+                        return Err(RuntimeError::new(format!("Duplicate key '{}' in {{#each}}", item_key), key_span));
+                    }
+                    nodes = set_keys(nodes, &item_key);
+                }
+                rendered.push(Node::Fragment(nodes));
+            }
+            Node::Fragment(rendered)
+        }
+        other => other,
+    })
+}
+
+/// Gives the elements rendered for one keyed `{#each}` item their key: the key itself for a
+/// single element, or the key and the element's position when the item renders several.
+/// Elements keyed by a nested `{#each}` keep their key, prefixed with this one. Components are
+/// flattened into the elements they render first, so those get keyed like any other.
+fn set_keys(nodes: Vec<Node>, key: &str) -> Vec<Node> {
+    let nodes = flatten_nodes(nodes);
+    let elements = nodes.iter().filter(|node| matches!(node, Node::Element { .. })).count();
+    let mut position = 0;
+    nodes
+        .into_iter()
+        .map(|node| match node {
+            Node::Element { tag_name, attributes, class_names, children, key: inner_key, span } => {
+                let key = match inner_key {
+                    Some(inner_key) => format!("{}-{}", key, inner_key),
+                    None if elements == 1 => key.to_string(),
+                    None => format!("{}-{}", key, position),
+                };
+                position += 1;
+                Node::Element { tag_name, attributes, class_names, children, key: Some(key), span }
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// Marks the elements a component renders with its scoped-style attribute. Content passed to a
+/// nested component is this component's own and gets marked too, but the nested body isn't.
+fn add_scope_attribute(nodes: Vec<Node>, scope: &str) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .map(|node| match node {
+            Node::Element { tag_name, mut attributes, class_names, children, key, span } => {
+                attributes.push(Attribute::new(scope, AttributeValue::Boolean));
+                Node::Element { tag_name, attributes, class_names, children: add_scope_attribute(children, scope), key, span }
+            }
+            Node::Fragment(children) => Node::Fragment(add_scope_attribute(children, scope)),
+            Node::Component { name, attributes, children, body, span } => {
+                Node::Component { name, attributes, children: add_scope_attribute(children, scope), body, span }
+            }
+            other => other,
+        })
+        .collect()
+}
+
+/// An attribute that is just one expression, like `disabled={locked}`, is dropped when the value
+/// is `false` or `null` and written without a value when it is `true`.
+fn evaluate_attributes(attributes: Vec<Attribute>, scope: &Scope) -> Result<Vec<Attribute>, RuntimeError> {
+    let mut evaluated = Vec::with_capacity(attributes.len());
+    for attribute in attributes {
+        let AttributeValue::Interpolated(parts) = &attribute.value else {
+            evaluated.push(attribute);
+            continue;
+        };
+
+        let value = match interpolate(parts, scope)? {
+            Value::Bool(false) | Value::Null => continue,
+            Value::Bool(true) => AttributeValue::Boolean,
+            value => AttributeValue::Literal(to_display_string(&value)),
+        };
+        evaluated.push(Attribute { value, ..attribute });
+    }
+    Ok(evaluated)
+}
+
+/// The value of an interpolated attribute: a lone expression keeps its value as it is,
+/// anything else is joined into a string.
+fn interpolate(parts: &[AttributePart], scope: &Scope) -> Result<Value, RuntimeError> {
+    if let [AttributePart::Expression { expression, span }] = parts {
+        return Expression::parse(expression, span.map(Span::inner))?.evaluate(scope);
+    }
+    let mut text = String::new();
+    for part in parts {
+        match part {
+            AttributePart::Literal(literal) => text.push_str(literal),
+            AttributePart::Expression { expression, span } => {
+                text.push_str(&to_display_string(&Expression::parse(expression, span.map(Span::inner))?.evaluate(scope)?));
+            }
+        }
+    }
+    Ok(Value::String(text))
+}
+
+/// Binds a component's props from the attributes it is used with, evaluated in the caller's scope,
+/// so `items={products}` passes the list itself. Props left out take their declared default.
+fn evaluate_props(
+    component: &Component,
+    attributes: &[Attribute],
+    scope: &Scope,
+    span: Option<Span>,
+) -> Result<Map<String, Value>, RuntimeError> {
+    let mut props = Map::new();
+    for attribute in attributes {
+        if !component.props.iter().any(|prop| prop.name == attribute.name) {
+            return Err(RuntimeError::new(format!("<{}> has no prop '{}'", component.name, attribute.name), attribute.span));
+        }
+        let value = match &attribute.value {
+            AttributeValue::Boolean => Value::Bool(true),
+            AttributeValue::Literal(text) => Value::String(text.clone()),
+            AttributeValue::Interpolated(parts) => interpolate(parts, scope)?,
+        };
+        props.insert(attribute.name.clone(), value);
+    }
+
+    for prop in &component.props {
+        if props.contains_key(&prop.name) {
+            continue;
+        }
+        let value = match &prop.default {
+            None => return Err(RuntimeError::new(format!("<{}> is missing its '{}' prop", component.name, prop.name), span)),
+            // Defaults are written in the component, so they only see literals
+            Some(AttributeValue::Interpolated(parts)) => {
+                interpolate(parts, &Scope::new(&Value::Null)).map_err(|err| err.in_file(&component.path))?
+            }
+            Some(AttributeValue::Literal(text)) => Value::String(text.clone()),
+            Some(AttributeValue::Boolean) => Value::Bool(true),
+        };
+        props.insert(prop.name.clone(), value);
+    }
+    Ok(props)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser};
+    use crate::engine::components::Component;
+    use dominate::dom::DomNode;
+    use serde_json::json;
+
+    fn text(node: &Node) -> String {
+        match node {
+            Node::Text { content, .. } => content.clone(),
+            Node::Element { children, .. } | Node::Fragment(children) => children.iter().map(text).collect(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_text_and_attributes() {
+        let source = r#"<button class="p-{size} flex" disabled={locked} hidden={hidden}>Hi {user.name}!</button>"#;
+        let nodes = Parser::new(Lexer::new(source)).parse_document().unwrap();
+        let data = json!({ "size": 4, "locked": true, "hidden": false, "user": { "name": "Ada" } });
+
+        let nodes = evaluate_nodes(nodes, &Scope::new(&data), &ComponentRegistry::new()).unwrap();
+        let Node::Element { attributes, class_names, children, .. } = &nodes[0] else { panic!("Expected element") };
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes[0].value, AttributeValue::Literal("p-4 flex".to_string()));
+        assert_eq!(attributes[1].value, AttributeValue::Boolean);
+        assert_eq!(class_names.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), ["p-4", "flex"]);
+        assert!(matches!(&children[1], Node::Text { content, .. } if content == "Ada"));
+    }
+
+    #[test]
+    fn test_evaluate_if_blocks() {
+        let source = "<p>{#if n > 10}big{:else if n > 1}some{:else}one{/if}</p>";
+        let nodes = Parser::new(Lexer::new(source)).parse_document().unwrap();
+        let render = |n: i64| {
+            let data = json!({ "n": n });
+            let nodes = evaluate_nodes(nodes.clone(), &Scope::new(&data), &ComponentRegistry::new()).unwrap();
+            let Node::Element { children, .. } = &nodes[0] else { panic!("Expected element") };
+            match &children[..] {
+                [Node::Fragment(children)] => match &children[..] {
+                    [Node::Text { content, .. }] => content.clone(),
+                    _ => panic!("Expected a single text node"),
+                },
+                _ => panic!("Expected a fragment"),
+            }
+        };
+        assert_eq!(render(20), "big");
+        assert_eq!(render(5), "some");
+        assert_eq!(render(1), "one");
+
+        let nodes = Parser::new(Lexer::new("{#if missing.flag}x{/if}")).parse_document().unwrap();
+        let err = evaluate_nodes(nodes, &Scope::new(&json!({})), &ComponentRegistry::new()).unwrap_err();
+        let span = err.span.unwrap();
+        assert_eq!(&"{#if missing.flag}x{/if}"[span.start_offset..span.end_offset], "missing");
+    }
+
+    #[test]
+    fn test_evaluate_keyed_each() {
+        let source = "<ul>{#each users as user, i (user.id)}<li>{i}: {user.name}</li>{:else}<li>Nobody</li>{/each}</ul>";
+        let nodes = Parser::new(Lexer::new(source)).parse_document().unwrap();
+        let data = json!({ "users": [{ "id": "a7", "name": "Ada" }, { "id": "g3", "name": "Grace" }] });
+
+        let dom = dominate::transform::transform(evaluate_nodes(nodes.clone(), &Scope::new(&data), &ComponentRegistry::new()).unwrap());
+        let DomNode::Element { children, .. } = &dom[0] else { panic!("Expected element") };
+        let keys: Vec<&str> = children
+            .iter()
+            .map(|child| match child {
+                DomNode::Element { key, .. } => key.as_str(),
+                _ => panic!("Expected <li>"),
+            })
+            .collect();
+        assert_eq!(keys, ["a7", "g3"]);
+
+        let empty = evaluate_nodes(nodes.clone(), &Scope::new(&json!({ "users": [] })), &ComponentRegistry::new()).unwrap();
+        let Node::Element { children, .. } = &empty[0] else { panic!("Expected element") };
+        assert!(matches!(&children[..], [Node::Fragment(nodes)] if nodes.len() == 1));
+
+        let duplicate = json!({ "users": [{ "id": 1 }, { "id": 1 }] });
+        let err = evaluate_nodes(nodes.clone(), &Scope::new(&duplicate), &ComponentRegistry::new()).unwrap_err();
+        assert_eq!(err.message, "Duplicate key '1' in {#each}");
+        let err = evaluate_nodes(nodes, &Scope::new(&json!({ "users": "nope" })), &ComponentRegistry::new()).unwrap_err();
+        assert_eq!(err.message, "{#each} needs an array, but 'users' is a string");
+    }
+
+    #[test]
+    fn test_expand_components_with_props() {
+        let card = r#"<props title tags={[]} size="md" />
+<div class="card card-{size}"><h2>{title}</h2>{#each tags as tag}<i>{tag}</i>{/each}</div>"#;
+        let mut components = ComponentRegistry::new();
+        components.register(Component::parse("Card", "Card.dts", card).unwrap()).unwrap();
+
+        let page = r#"<main><Card title={user.name} tags={user.tags} /></main>"#;
+        let nodes = Parser::new(Lexer::new(page)).parse_document().unwrap();
+        let data = json!({ "user": { "name": "Ada", "tags": ["x", "y"] } });
+        let nodes = evaluate_nodes(nodes, &Scope::new(&data), &components).unwrap();
+        let Node::Element { children, .. } = &nodes[0] else { panic!("Expected <main>") };
+        let [Node::Component { body: Some(body), .. }] = &children[..] else { panic!("Expected the expanded component") };
+        let Node::Element { attributes, class_names, .. } = &body[0] else { panic!("Expected the card's <div>") };
+        assert_eq!(attributes[0].value, AttributeValue::Literal("card card-md".to_string()));
+        assert_eq!(class_names.len(), 2);
+        assert_eq!(text(&body[0]), "Adaxy");
+
+        // Left out, `tags` takes its default, an empty list
+        let nodes = Parser::new(Lexer::new("<Card title='Bo' size='lg' />")).parse_document().unwrap();
+        let nodes = evaluate_nodes(nodes, &Scope::new(&data), &components).unwrap();
+        let [Node::Component { body: Some(body), .. }] = &nodes[..] else { panic!("Expected the expanded component") };
+        assert_eq!(text(&body[0]), "Bo");
+
+        let nodes = Parser::new(Lexer::new("<Card heading='x' />")).parse_document().unwrap();
+        let err = evaluate_nodes(nodes, &Scope::new(&data), &components).unwrap_err();
+        assert_eq!(err.message, "<Card> has no prop 'heading'");
+        let nodes = Parser::new(Lexer::new("<Card />")).parse_document().unwrap();
+        let err = evaluate_nodes(nodes, &Scope::new(&data), &components).unwrap_err();
+        assert_eq!(err.message, "<Card> is missing its 'title' prop");
+    }
+
+    #[test]
+    fn test_recursive_components() {
+        let mut components = ComponentRegistry::new();
+        components.register(Component::parse("Card", "Card.dts", "<div><Card /></div>").unwrap()).unwrap();
+        components.register(Component::parse("Ping", "Ping.dts", "<p><Pong /></p>").unwrap()).unwrap();
+        components.register(Component::parse("Pong", "Pong.dts", "<Ping />").unwrap()).unwrap();
+
+        for (page, message, file) in [("<Card />", "<Card> uses itself", "Card.dts"), ("<Ping />", "<Ping> uses itself", "Pong.dts")] {
+            let nodes = Parser::new(Lexer::new(page)).parse_document().unwrap();
+            let err = evaluate_nodes(nodes, &Scope::new(&json!({})), &components).unwrap_err();
+            assert_eq!(err.message, message);
+            assert_eq!(err.file.as_deref(), Some(file));
+        }
+
+        // A component passed to itself as content isn't recursion
+        components.register(Component::parse("Box", "Box.dts", "<div><slot /></div>").unwrap()).unwrap();
+        let nodes = Parser::new(Lexer::new("<Box><Box>x</Box></Box>")).parse_document().unwrap();
+        assert!(evaluate_nodes(nodes, &Scope::new(&json!({})), &components).is_ok());
+    }
+
+    #[test]
+    fn test_fill_default_and_named_slots() {
+        let layout = r#"<div><aside><slot name="sidebar">No sidebar</slot></aside><main><slot /></main><footer><slot name="footer">(c) us</slot></footer></div>"#;
+        let mut components = ComponentRegistry::new();
+        components.register(Component::parse("Layout", "Layout.dts", layout).unwrap()).unwrap();
+
+        let page = r#"<Layout>
+  <nav slot="sidebar">Links</nav>
+  <p>Hello {name}</p>
+</Layout>"#;
+        let nodes = Parser::new(Lexer::new(page)).parse_document().unwrap();
+        let nodes = evaluate_nodes(nodes, &Scope::new(&json!({ "name": "Ada" })), &components).unwrap();
+        let dom = dominate::transform::transform(nodes);
+
+        fn dom_text(node: &DomNode) -> String {
+            match node {
+                DomNode::Element { children, .. } => children.iter().map(dom_text).collect(),
+                DomNode::Text(text) | DomNode::Expression(text) => text.clone(),
+            }
+        }
+        let DomNode::Element { children, .. } = &dom[0] else { panic!("Expected the layout's <div>") };
+        assert_eq!(children.iter().map(dom_text).collect::<Vec<_>>(), ["Links", "Hello Ada", "(c) us"]);
+        let DomNode::Element { children: aside, .. } = &children[0] else { panic!("Expected <aside>") };
+        assert!(matches!(&aside[0], DomNode::Element { tag, attributes, .. } if tag == "nav" && attributes.is_empty()));
+
+        // Content for a slot the component doesn't have is an error, pointing at the content
+        components.register(Component::parse("Box", "Box.dts", r#"<div><slot name="side" /></div>"#).unwrap()).unwrap();
+        components.register(Component::parse("Plain", "Plain.dts", "<hr>").unwrap()).unwrap();
+        let page = "<Box>\n  <nav slot=\"sidebar\">Links</nav>\n</Box>";
+        let nodes = Parser::new(Lexer::new(page)).parse_document().unwrap();
+        let err = evaluate_nodes(nodes, &Scope::new(&json!({})), &components).unwrap_err();
+        assert_eq!(err.message, r#"<Box> has no <slot name="sidebar"> for this content"#);
+        let span = err.span.unwrap();
+        assert_eq!(&page[span.start_offset..span.end_offset], r#"<nav slot="sidebar">Links</nav>"#);
+
+        let nodes = Parser::new(Lexer::new("<Plain>\n</Plain><Plain>\n  <p>dropped</p>\n</Plain>")).parse_document().unwrap();
+        let err = evaluate_nodes(nodes, &Scope::new(&json!({})), &components).unwrap_err();
+        assert_eq!(err.message, "<Plain> has no <slot /> for this content");
+    }
+}
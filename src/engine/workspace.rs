@@ -4,8 +4,10 @@ use std::collections::HashMap;
 use std::path::Path;
 use notify::{recommended_watcher, RecursiveMode, Watcher};
 use std::sync::mpsc::channel;
+use crate::error::DesignTimeError;
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WorkspaceConfig {
     pub project: ProjectInfo,
     pub packages: Option<Packages>,
@@ -18,6 +20,7 @@ pub struct WorkspaceConfig {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProjectInfo {
     pub name: String,
     pub version: String,
@@ -25,6 +28,7 @@ pub struct ProjectInfo {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Packages {
     pub dependencies: Option<Vec<String>>,
     pub dev_dependencies: Option<Vec<String>>,
@@ -32,6 +36,7 @@ pub struct Packages {
 
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct StyleMan {
     #[serde(default)]
     pub enabled: bool,
@@ -39,12 +44,14 @@ pub struct StyleMan {
 
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Scan {
     pub include: Option<Vec<String>>,
     pub exclude: Option<Vec<String>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Routes {
     pub auto_register: Option<bool>,
     pub pages_dir: Option<String>,
@@ -53,6 +60,7 @@ pub struct Routes {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Theme {
     pub default: Option<String>,
     pub available: Option<Vec<String>>,
@@ -60,6 +68,7 @@ pub struct Theme {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Components {
     pub auto_register: Option<bool>,
     pub directories: Option<Vec<String>>,
@@ -67,6 +76,7 @@ pub struct Components {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Build {
     pub output_dir: Option<String>,
     pub target: Option<Vec<String>>,
@@ -75,6 +85,7 @@ pub struct Build {
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DevServer {
     pub port: Option<u16>,
     pub hot_reload: Option<bool>,
@@ -106,26 +117,35 @@ pub fn watch_workspace_config(path: &Path, on_change: impl Fn() + Send + 'static
     });
 }
 
-pub fn validate_and_load_workspace<P: AsRef<std::path::Path>>(path: P) -> Result<WorkspaceConfig, anyhow::Error> {
+/// Loads designtime.json and validates it against the schema, reporting every problem found.
+pub fn validate_and_load_workspace<P: AsRef<std::path::Path>>(path: P) -> Result<WorkspaceConfig, Vec<DesignTimeError>> {
     let schema_str = include_str!("../../designtime.schema.json"); // todo: make this configurable
-    let config_str = std::fs::read_to_string(path)?;
+    let config_str = std::fs::read_to_string(path).map_err(|e| vec![e.into()])?;
 
-    let schema: Value = serde_json::from_str(schema_str)?;
-    let config: Value = serde_json::from_str(&config_str)?;
+    let schema: Value = serde_json::from_str(schema_str).map_err(|e| vec![config_error(e)])?;
+    let config: Value = serde_json::from_str(&config_str).map_err(|e| vec![config_error(e)])?;
 
-    let validator = jsonschema::validator_for(&schema)?;
+    let validator = jsonschema::validator_for(&schema).map_err(|e| vec![config_error(e)])?;
 
-    let errors: Vec<String> = validator
-        .validate(&config)
-        .err()
-        .map(|e| e.to_string())
-        .into_iter()
+    let errors: Vec<DesignTimeError> = validator
+        .iter_errors(&config)
+        .map(|e| DesignTimeError::ConfigError {
+            path: e.instance_path.to_string(),
+            message: e.to_string(),
+        })
         .collect();
 
     if !errors.is_empty() {
-        anyhow::bail!("Config validation errors:\n{}", errors.join("\n"));
+        return Err(errors);
     }
 
-    let workspace_config = serde_json::from_value(config)?;
+    let workspace_config = serde_json::from_value(config).map_err(|e| vec![config_error(e)])?;
     Ok(workspace_config)
 }
+
+fn config_error(err: impl std::fmt::Display) -> DesignTimeError {
+    DesignTimeError::ConfigError {
+        path: String::new(),
+        message: err.to_string(),
+    }
+}
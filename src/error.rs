@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+// Spans live in the AST crate so nodes can carry them too
+pub use designtime_ast::Span;
+
 #[derive(Error, Debug)]
 pub enum DesignTimeError {
     #[error("Lexer error at {span:?}: {message}")]
@@ -23,6 +26,13 @@ pub enum DesignTimeError {
     #[error("Runtime error: {0}")]
     RuntimeError(String),
 
+    /// designtime.json failed to load or validate; `path` is the JSON pointer of the bad value
+    #[error("Config error at '{path}': {message}")]
+    ConfigError {
+        path: String,
+        message: String,
+    },
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -31,6 +41,44 @@ pub enum DesignTimeError {
 }
 
 impl DesignTimeError {
+    /// Stable code identifying the kind of error, shown as `error[E0200]`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::LexerError { .. } => "E0100",
+            Self::ParserError { .. } => "E0200",
+            Self::CompilerError(_) => "E0300",
+            Self::RuntimeError(_) => "E0400",
+            Self::ConfigError { .. } => "E0600",
+            Self::IoError(_) => "E0500",
+            Self::Unknown(_) => "E0900",
+        }
+    }
+
+    /// The error message without the location prefix used by `Display`.
+    pub fn message(&self) -> String {
+        match self {
+            Self::LexerError { message, .. } | Self::ParserError { message, .. } => message.clone(),
+            Self::CompilerError(msg) | Self::RuntimeError(msg) | Self::Unknown(msg) => msg.clone(),
+            Self::ConfigError { path, message } if path.is_empty() => message.clone(),
+            Self::ConfigError { path, message } => format!("{path}: {message}"),
+            Self::IoError(err) => err.to_string(),
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::LexerError { span, .. } | Self::ParserError { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    pub fn suggestion(&self) -> Option<&str> {
+        match self {
+            Self::LexerError { suggestion, .. } | Self::ParserError { suggestion, .. } => suggestion.as_deref(),
+            _ => None,
+        }
+    }
+
     /// Creates a new instance of the error with the same variant and data
     pub fn clone_error(&self) -> Self {
         match self {
@@ -46,17 +94,20 @@ impl DesignTimeError {
             },
             Self::CompilerError(msg) => Self::CompilerError(msg.clone()),
             Self::RuntimeError(msg) => Self::RuntimeError(msg.clone()),
+            Self::ConfigError { path, message } => Self::ConfigError {
+                path: path.clone(),
+                message: message.clone(),
+            },
             Self::IoError(err) => Self::IoError(std::io::Error::new(err.kind(), err.to_string())),
             Self::Unknown(msg) => Self::Unknown(msg.clone()),
         }
     }
 }
 
-// Span struct to represent start and end byte offsets or line/col
-#[derive(Debug, Clone, Copy)]
-pub struct Span {
-    pub start_line: usize,
-    pub start_column: usize,
-    pub end_line: usize,
-    pub end_column: usize,
-}
\ No newline at end of file
+/// An error in one source file, kept with the file's text so a diagnostic can show the code.
+#[derive(Debug)]
+pub struct FileError {
+    pub path: String,
+    pub source: String,
+    pub error: DesignTimeError,
+}
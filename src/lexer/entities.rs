@@ -0,0 +1,106 @@
+// HTML character references and backslash escapes
+
+/// Longest name or number allowed between `&` and `;`.
+const MAX_REFERENCE_LEN: usize = 32;
+
+/// Decodes the character reference at the start of `input` (which must begin with `&`),
+/// returning the decoded character and how many bytes of `input` it used.
+///
+/// Handles named references such as `&amp;` and numeric ones such as `&#169;` or `&#x2014;`.
+/// Returns `None` for anything unrecognised, so the `&` can be kept as-is.
+pub fn decode_entity(input: &str) -> Option<(char, usize)> {
+    let body = input.strip_prefix('&')?;
+    // Only look as far as a reference could reach, so text full of bare '&'s stays linear
+    let end = body.bytes().take(MAX_REFERENCE_LEN).position(|b| !(b.is_ascii_alphanumeric() || b == b'#'))?;
+    if body.as_bytes()[end] != b';' {
+        return None;
+    }
+    let name = &body[..end];
+
+    let decoded = if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse::<u32>().ok()?,
+        };
+        // Out-of-range and surrogate code points decode to the replacement character, as browsers do
+        char::from_u32(code).filter(|_| code != 0).unwrap_or('\u{FFFD}')
+    } else {
+        named_entity(name)?
+    };
+
+    Some((decoded, end + 2))
+}
+
+/// Decodes a backslash escape, given the character that follows the `\`.
+/// `quote` is the delimiter of the surrounding string literal, if any.
+pub fn decode_escape(c: char, quote: Option<char>) -> Option<char> {
+    match c {
+        'n' if quote.is_some() => Some('\n'),
+        't' if quote.is_some() => Some('\t'),
+        'r' if quote.is_some() => Some('\r'),
+        '"' | '\'' if quote.is_some() => Some(c),
+        '\\' | '<' | '>' | '{' | '}' | '&' => Some(c),
+        _ => None,
+    }
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{A0}',
+        "lbrace" => '{',
+        "rbrace" => '}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "deg" => '°',
+        "times" => '×',
+        "divide" => '÷',
+        "plusmn" => '±',
+        "middot" => '·',
+        "bull" => '•',
+        "hellip" => '…',
+        "ndash" => '–',
+        "mdash" => '—',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "larr" => '←',
+        "rarr" => '→',
+        "uarr" => '↑',
+        "darr" => '↓',
+        "euro" => '€',
+        "pound" => '£',
+        "yen" => '¥',
+        "cent" => '¢',
+        "sect" => '§',
+        "para" => '¶',
+        "shy" => '\u{AD}',
+        "zwj" => '\u{200D}',
+        "zwnj" => '\u{200C}',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_end_at_the_first_non_name_character() {
+        assert_eq!(decode_entity("&amp;x"), Some(('&', 5)));
+        assert_eq!(decode_entity("&#x2014;"), Some(('—', 8)));
+        assert_eq!(decode_entity("&amp x;"), None);
+        assert_eq!(decode_entity("&a&b;"), None);
+        assert_eq!(decode_entity(&format!("&{};", "0".repeat(MAX_REFERENCE_LEN))), None);
+        assert_eq!(decode_entity(&format!("&#{}65;", "0".repeat(MAX_REFERENCE_LEN - 4))), Some(('A', MAX_REFERENCE_LEN + 1)));
+    }
+}
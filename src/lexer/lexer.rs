@@ -1,8 +1,11 @@
 // Simple lexer
 
 use crate::Span;
-use crate::lexer::line_tracker::LineTracker;
-use crate::lexer::tokens::{Token, TokenKind};
+use std::borrow::Cow;
+use designtime_ast::{is_component_tag, is_raw_text_element};
+use crate::lexer::entities::{decode_entity, decode_escape};
+use crate::lexer::line_tracker::{LineTracker, Mark};
+use crate::lexer::tokens::{StringPart, Token, TokenKind};
 
 pub struct Lexer<'a> {
     input: &'a str,
@@ -10,6 +13,13 @@ pub struct Lexer<'a> {
     peeked: Option<char>,
     line_tracker: LineTracker,
     in_tag: bool,
+    after_eq: bool,
+    /// Name of the tag being opened, between `<name` and its `>`
+    opening_tag: Option<String>,
+    /// Set when a `<script>` or `<style>` opening tag ends, so its content is lexed as `RawText`
+    raw_text_tag: Option<String>,
+    after_lt: bool,
+    after_slash: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -20,9 +30,19 @@ impl<'a> Lexer<'a> {
             peeked: None,
             line_tracker: LineTracker::new(),
             in_tag: false,
+            after_eq: false,
+            opening_tag: None,
+            raw_text_tag: None,
+            after_lt: false,
+            after_slash: false,
         }
     }
 
+    /// The full source text being lexed.
+    pub fn source(&self) -> &'a str {
+        self.input
+    }
+
     fn next_char(&mut self) -> Option<char> {
         let next = if let Some(c) = self.peeked.take() {
             Some(c)
@@ -42,182 +62,280 @@ impl<'a> Lexer<'a> {
         self.peeked
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    /// Returns the character after the peeked one without consuming either.
+    fn peek_second_char(&mut self) -> Option<char> {
+        self.peek_char();
+        self.chars.clone().next()
+    }
+
+    pub fn next_token(&mut self) -> Token<'a> {
+        let token = match self.raw_text_tag.take() {
+            Some(tag_name) => self.lex_raw_text(&tag_name),
+            None => self.lex_token(),
+        };
 
-        let (line, column) = self.line_tracker.position();
+        match &token.kind {
+            TokenKind::Lt => self.opening_tag = None,
+            TokenKind::Name(name) if self.after_lt => self.opening_tag = Some(name.to_string()),
+            TokenKind::Gt => {
+                // `<script />` has no content to read, and a `<Script>` component's content is markup
+                if let Some(tag_name) = self.opening_tag.take()
+                    && !self.after_slash
+                    && is_raw_text_element(&tag_name)
+                    && !is_component_tag(&tag_name)
+                {
+                    self.raw_text_tag = Some(tag_name);
+                }
+            }
+            _ => {}
+        }
+        self.after_lt = token.kind == TokenKind::Lt;
+        self.after_slash = token.kind == TokenKind::Slash;
 
+        token
+    }
+
+    fn lex_token(&mut self) -> Token<'a> {
+        // Whitespace outside tags is text; the parser decides what to keep
         if self.in_tag {
+            self.skip_whitespace();
+        }
+
+        let start = self.line_tracker.mark();
+
+        if self.in_tag {
+            // Right after '=' a value may be unquoted, e.g. colspan=2. As in HTML it runs to whitespace
+            // or '>', so `href=/docs/>` keeps its slash and only `/>` after whitespace self-closes
+            if std::mem::take(&mut self.after_eq)
+                && let Some(c) = self.peek_char()
+                && !matches!(c, '"' | '\'' | '>')
+            {
+                let value = self.read_value(None, |_, c| c.is_whitespace() || c == '>');
+                return self.token(value, start);
+            }
+
             // We are inside a tag - read tag tokens
             match self.peek_char() {
                 Some('>') => {
                     self.next_char();
                     self.in_tag = false;
-                    Token {
-                        kind: TokenKind::Gt,
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    self.token(TokenKind::Gt, start)
                 }
                 Some('=') => {
                     self.next_char();
-                    Token {
-                        kind: TokenKind::Eq,
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
-                }
-                Some('"') => {
-                    self.next_char(); // consume opening quote
-                    let mut value = String::new();
-                    while let Some(ch) = self.peek_char() {
-                        if ch == '"' {
-                            self.next_char(); // consume closing quote
-                            break;
-                        }
-                        value.push(ch);
-                        self.next_char();
-                    }
-                    Token {
-                        kind: TokenKind::StringLiteral(value),
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    self.after_eq = true;
+                    self.token(TokenKind::Eq, start)
                 }
-                Some('\'') => {
+                Some(quote @ ('"' | '\'')) => {
                     self.next_char(); // consume opening quote
-                    let mut value = String::new();
-                    while let Some(ch) = self.peek_char() {
-                        if ch == '\'' {
-                            self.next_char(); // consume closing quote
-                            break;
-                        }
-                        value.push(ch);
-                        self.next_char();
-                    }
-                    Token {
-                        kind: TokenKind::StringLiteral(value),
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    let value = self.read_value(Some(quote), |_, c| c == quote);
+                    self.next_char(); // consume closing quote
+                    self.token(value, start)
                 }
                 Some('/') => {
                     self.next_char();
-                    Token {
-                        kind: TokenKind::Slash,
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    self.token(TokenKind::Slash, start)
                 }
                 Some(c) if is_name_start_char(c) => {
-                    let mut name = String::new();
-                    while let Some(next_c) = self.peek_char() {
-                        if is_name_char(next_c) {
-                            name.push(next_c);
-                            self.next_char();
-                        } else {
-                            break;
-                        }
-                    }
-                    Token {
-                        kind: TokenKind::Name(name),
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
+                    while self.peek_char().is_some_and(is_name_char) {
+                        self.next_char();
                     }
+                    let name = &self.input[start.offset..self.line_tracker.offset()];
+                    self.token(TokenKind::Name(Cow::Borrowed(name)), start)
                 }
                 Some(c) => {
                     // Unexpected char inside tag - consume it anyway
                     self.next_char();
-                    Token {
-                        kind: TokenKind::Unknown(c),
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    self.token(TokenKind::Unknown(c), start)
                 }
-                None => Token {
-                    kind: TokenKind::EOF,
-                    span: Span {
-                        start_line: line,
-                        start_column: column,
-                        end_line: line,
-                        end_column: column,
-                    },
-                },
+                None => self.token(TokenKind::EOF, start),
             }
         } else {
             // Outside tag - should start with '<' or text
             match self.peek_char() {
+                Some('<') if self.rest().starts_with("<!--") => self.lex_comment(start, "<!--", "-->"),
+                Some('{') if self.rest().starts_with("{/*") => self.lex_comment(start, "{/*", "*/}"),
+                Some('{') => {
+                    let expression = self.read_expression();
+                    self.token(TokenKind::Expression(expression), start)
+                }
                 Some('<') => {
                     self.next_char();
                     self.in_tag = true;
-                    Token {
-                        kind: TokenKind::Lt,
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    self.token(TokenKind::Lt, start)
                 }
                 Some(_) => {
-                    // Read all text until next '<'
-                    let mut text = String::new();
-                    while let Some(next_c) = self.peek_char() {
-                        if next_c == '<' {
-                            break;
-                        }
-                        text.push(next_c);
-                        self.next_char();
-                    }
-                    Token {
-                        kind: TokenKind::InnerText(text),
-                        span: Span {
-                            start_line: line,
-                            start_column: column,
-                            end_line: line,
-                            end_column: column,
-                        },
-                    }
+                    // Read all text until next '<', expression or comment
+                    let text = self.read_text(None, |_, c| c == '<' || c == '{');
+                    self.token(TokenKind::InnerText(text), start)
+                }
+                None => self.token(TokenKind::EOF, start),
+            }
+        }
+    }
+
+    /// Reads everything up to the `</tag_name` that closes a raw text element, without
+    /// decoding entities or looking for markup.
+    fn lex_raw_text(&mut self, tag_name: &str) -> Token<'a> {
+        let start = self.line_tracker.mark();
+        let closer = format!("</{}", tag_name);
+
+        while self.peek_char().is_some() {
+            let rest = self.rest();
+            if rest.get(..closer.len()).is_some_and(|head| head.eq_ignore_ascii_case(&closer)) {
+                break;
+            }
+            self.next_char();
+        }
+        let content = &self.input[start.offset..self.line_tracker.offset()];
+        self.token(TokenKind::RawText(Cow::Borrowed(content)), start)
+    }
+
+    /// Reads a comment from `open` up to and including `close`, or to the end of input if unterminated.
+    fn lex_comment(&mut self, start: Mark, open: &str, close: &str) -> Token<'a> {
+        for _ in open.chars() {
+            self.next_char();
+        }
+
+        let content_start = self.line_tracker.offset();
+        let mut content_end = self.input.len();
+        while self.peek_char().is_some() {
+            if self.rest().starts_with(close) {
+                content_end = self.line_tracker.offset();
+                for _ in close.chars() {
+                    self.next_char();
+                }
+                break;
+            }
+            self.next_char();
+        }
+        let content = &self.input[content_start..content_end];
+        self.token(TokenKind::Comment(Cow::Borrowed(content)), start)
+    }
+
+    /// Reads an attribute value up to the first character `at_end` accepts. A value with
+    /// `{...}` expressions in it becomes `Interpolated`, otherwise it is a `StringLiteral`.
+    fn read_value(&mut self, quote: Option<char>, at_end: impl Fn(&mut Self, char) -> bool) -> TokenKind<'a> {
+        let mut parts = Vec::new();
+        loop {
+            let text = self.read_text(quote, |lexer, c| c == '{' || at_end(lexer, c));
+            if !text.is_empty() {
+                parts.push(StringPart::Literal(text));
+            }
+            if self.peek_char() != Some('{') {
+                break;
+            }
+            let start = self.line_tracker.mark();
+            let expression = self.read_expression();
+            parts.push(StringPart::Expression(expression, self.span_from(start)));
+        }
+
+        match parts.pop() {
+            None => TokenKind::StringLiteral(Cow::Borrowed("")),
+            Some(StringPart::Literal(text)) if parts.is_empty() => TokenKind::StringLiteral(text),
+            Some(last) => {
+                parts.push(last);
+                TokenKind::Interpolated(parts)
+            }
+        }
+    }
+
+    /// Reads a `{...}` expression and returns the source between the braces. Braces inside
+    /// string literals don't count, and nested ones must balance. An unterminated expression
+    /// runs to the end of input.
+    fn read_expression(&mut self) -> Cow<'a, str> {
+        self.next_char(); // consume '{'
+        let start = self.line_tracker.offset();
+        let mut end = self.input.len();
+        let mut depth = 0;
+        let mut in_string: Option<char> = None;
+
+        while let Some(c) = self.peek_char() {
+            match (in_string, c) {
+                (Some(_), '\\') => {
+                    self.next_char();
                 }
-                None => Token {
-                    kind: TokenKind::EOF,
-                    span: Span {
-                        start_line: line,
-                        start_column: column,
-                        end_line: line,
-                        end_column: column,
-                    },
-                },
+                (Some(quote), c) if c == quote => in_string = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'' | '`') => in_string = Some(c),
+                (None, '{') => depth += 1,
+                (None, '}') if depth == 0 => {
+                    end = self.line_tracker.offset();
+                    self.next_char(); // consume '}'
+                    break;
+                }
+                (None, '}') => depth -= 1,
+                _ => {}
+            }
+            self.next_char();
+        }
+        Cow::Borrowed(&self.input[start..end])
+    }
+
+    /// Reads text up to the first character `at_end` accepts, decoding references along the way.
+    /// The text is borrowed from the source unless a reference had to be decoded.
+    fn read_text(&mut self, quote: Option<char>, mut at_end: impl FnMut(&mut Self, char) -> bool) -> Cow<'a, str> {
+        let input = self.input;
+        let start = self.line_tracker.offset();
+        let mut decoded: Option<String> = None;
+
+        while let Some(c) = self.peek_char() {
+            if at_end(self, c) {
+                break;
             }
+            let offset = self.line_tracker.offset();
+            if let Some(reference) = self.read_reference(quote) {
+                decoded.get_or_insert_with(|| input[start..offset].to_string()).push(reference);
+                continue;
+            }
+            self.next_char();
+            if let Some(text) = &mut decoded {
+                text.push(c);
+            }
+        }
+
+        match decoded {
+            Some(text) => Cow::Owned(text),
+            None => Cow::Borrowed(&input[start..self.line_tracker.offset()]),
+        }
+    }
+
+    /// Decodes and consumes a character reference (`&lt;`) or backslash escape (`\<`)
+    /// at the current position. Returns `None`, consuming nothing, if there isn't one.
+    fn read_reference(&mut self, quote: Option<char>) -> Option<char> {
+        let (decoded, len) = match self.peek_char()? {
+            '&' => decode_entity(self.rest())?,
+            '\\' => (self.peek_second_char().and_then(|c| decode_escape(c, quote))?, 2),
+            _ => return None,
+        };
+
+        let end = self.line_tracker.offset() + len;
+        while self.line_tracker.offset() < end {
+            self.next_char();
+        }
+        Some(decoded)
+    }
+
+    /// The unconsumed source, starting at the peeked character if there is one.
+    fn rest(&self) -> &'a str {
+        &self.input[self.line_tracker.offset()..]
+    }
+
+    /// Builds a token spanning from `start` up to the current position.
+    fn token(&self, kind: TokenKind<'a>, start: Mark) -> Token<'a> {
+        Token { kind, span: self.span_from(start) }
+    }
+
+    /// The span from `start` up to the current position.
+    fn span_from(&self, start: Mark) -> Span {
+        let (end_line, end_column) = self.line_tracker.position();
+        Span {
+            start_line: start.line,
+            start_column: start.column,
+            end_line,
+            end_column,
+            start_offset: start.offset,
+            end_offset: self.line_tracker.offset(),
         }
     }
 
@@ -236,6 +354,109 @@ fn is_name_start_char(c: char) -> bool {
     c.is_alphabetic()
 }
 
+/// ':' allows namespaced names such as `xlink:href` and aliased components like `MUI:Button`
 fn is_name_char(c: char) -> bool {
-    c.is_alphanumeric() || c == '-' || c == '_'
+    c.is_alphanumeric() || c == '-' || c == '_' || c == ':'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<Token<'_>> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            let done = token.kind == TokenKind::EOF;
+            tokens.push(token);
+            if done {
+                break tokens;
+            }
+        }
+    }
+
+    fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+        Span {
+            start_line: start.0,
+            start_column: start.1,
+            start_offset: start.2,
+            end_line: end.0,
+            end_column: end.1,
+            end_offset: end.2,
+        }
+    }
+
+    #[test]
+    fn test_token_spans_cover_whole_token() {
+        let tokens = lex(r#"<section class="a b">"#);
+
+        assert_eq!(tokens[0].span, span((1, 0, 0), (1, 1, 1)));
+        assert_eq!(tokens[1].kind, TokenKind::Name("section".into()));
+        assert_eq!(tokens[1].span, span((1, 1, 1), (1, 8, 8)));
+        assert_eq!(tokens[3].span, span((1, 14, 14), (1, 15, 15)));
+        assert_eq!(tokens[4].kind, TokenKind::StringLiteral("a b".into()));
+        assert_eq!(tokens[4].span, span((1, 15, 15), (1, 20, 20)));
+    }
+
+    #[test]
+    fn test_multiline_text_span_and_byte_offsets() {
+        let source = "<p>héllo\nwörld</p>";
+        let tokens = lex(source);
+
+        let text = &tokens[3];
+        assert_eq!(text.kind, TokenKind::InnerText("héllo\nwörld".into()));
+        assert_eq!(text.span, span((1, 3, 3), (2, 5, 16)));
+        assert_eq!(&source[text.span.start_offset..text.span.end_offset], "héllo\nwörld");
+    }
+
+    #[test]
+    fn test_comments_are_single_tokens() {
+        let tokens = lex("<!-- a <b> c -->text{/* note */}more");
+
+        assert_eq!(tokens[0].kind, TokenKind::Comment(" a <b> c ".into()));
+        assert_eq!(tokens[0].span.end_offset, 16);
+        assert_eq!(tokens[1].kind, TokenKind::InnerText("text".into()));
+        assert_eq!(tokens[2].kind, TokenKind::Comment(" note ".into()));
+        assert_eq!(tokens[3].kind, TokenKind::InnerText("more".into()));
+        assert_eq!(tokens[4].kind, TokenKind::EOF);
+    }
+
+    #[test]
+    fn test_entities_and_escapes_are_decoded() {
+        let source = r#"<a title="say \"hi\" &amp; go" data-x=a&lt;b>1 &lt; 2 &#x2014; \<b\> &bogus; &#169;</a>"#;
+        let tokens = lex(source);
+
+        assert_eq!(tokens[4].kind, TokenKind::StringLiteral(r#"say "hi" & go"#.into()));
+        assert_eq!(tokens[7].kind, TokenKind::StringLiteral("a<b".into()));
+        assert_eq!(tokens[9].kind, TokenKind::InnerText("1 < 2 — <b> &bogus; ©".into()));
+        // Spans still cover the encoded source
+        let text = tokens[9].span;
+        assert_eq!(&source[text.start_offset..text.end_offset], r"1 &lt; 2 &#x2014; \<b\> &bogus; &#169;");
+    }
+
+    #[test]
+    fn test_text_borrows_from_source_unless_decoded() {
+        let tokens = lex(r#"<p title="plain">as typed &amp; decoded</p>"#);
+
+        assert!(matches!(&tokens[1].kind, TokenKind::Name(Cow::Borrowed("p"))));
+        assert!(matches!(&tokens[4].kind, TokenKind::StringLiteral(Cow::Borrowed("plain"))));
+        assert!(matches!(&tokens[6].kind, TokenKind::InnerText(Cow::Owned(text)) if text == "as typed & decoded"));
+    }
+
+    #[test]
+    fn test_expressions_in_text_and_attributes() {
+        let tokens = lex(r#"<p class="p-{size} {flags['a}']}">Hello {user.name}! \{not} {/* note */}</p>"#);
+
+        let TokenKind::Interpolated(parts) = &tokens[4].kind else { panic!("Expected interpolated value") };
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], StringPart::Literal("p-".into()));
+        assert!(matches!(&parts[1], StringPart::Expression(expr, span) if expr == "size" && span.start_offset == 12 && span.end_offset == 18));
+        assert_eq!(parts[2], StringPart::Literal(" ".into()));
+        assert!(matches!(&parts[3], StringPart::Expression(expr, _) if expr == "flags['a}']"));
+        assert_eq!(tokens[6].kind, TokenKind::InnerText("Hello ".into()));
+        assert_eq!(tokens[7].kind, TokenKind::Expression("user.name".into()));
+        assert_eq!(tokens[8].kind, TokenKind::InnerText("! {not} ".into()));
+        assert_eq!(tokens[9].kind, TokenKind::Comment(" note ".into()));
+    }
 }
@@ -1,14 +1,24 @@
 pub struct LineTracker {
     pub line: usize,
     pub column: usize,
+    pub offset: usize,
+}
+
+/// A saved position in the source, used as the start of a token's span.
+#[derive(Debug, Clone, Copy)]
+pub struct Mark {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
 }
 
 impl LineTracker {
     pub fn new() -> Self {
-        Self { line: 1, column: 0 }
+        Self { line: 1, column: 0, offset: 0 }
     }
 
     pub fn advance(&mut self, c: char) {
+        self.offset += c.len_utf8();
         if c == '\n' {
             self.line += 1;
             self.column = 0;
@@ -20,4 +30,13 @@ impl LineTracker {
     pub fn position(&self) -> (usize, usize) {
         (self.line, self.column)
     }
+
+    /// Byte offset of the next character in the source.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn mark(&self) -> Mark {
+        Mark { line: self.line, column: self.column, offset: self.offset }
+    }
 }
@@ -1,7 +1,8 @@
 pub mod lexer;
 pub mod tokens;
 pub mod line_tracker;
+pub mod entities;
 
 pub use lexer::Lexer;
-pub use tokens::{Token, TokenKind};
-pub use line_tracker::LineTracker;
\ No newline at end of file
+pub use tokens::{StringPart, Token, TokenKind};
+pub use line_tracker::{LineTracker, Mark};
\ No newline at end of file
@@ -1,24 +1,79 @@
 use crate::Span;
+use std::borrow::Cow;
 
+/// Text-carrying tokens borrow from the source and only own their text when
+/// an entity or escape had to be decoded.
 #[derive(Debug, Clone, PartialEq)]
-pub enum TokenKind {
+pub enum TokenKind<'a> {
     Lt, // <
     Gt, // >
 
     Slash, // /
     Eq,    // =
 
-    Name(String),          // tag names like div, span
-    Text(String),          // text
-    InnerText(String),     // inner text
-    StringLiteral(String), // string literal
-    Unknown(char),         // unknown char
+    Name(Cow<'a, str>),          // tag names like div, span
+    Text(Cow<'a, str>),          // text
+    InnerText(Cow<'a, str>),     // inner text
+    StringLiteral(Cow<'a, str>), // string literal
+    Comment(Cow<'a, str>),       // <!-- comment --> or {/* comment */}
+    RawText(Cow<'a, str>),       // verbatim content of <script> or <style>
+    Expression(Cow<'a, str>),    // {user.name}, the source between the braces
+    Interpolated(Vec<StringPart<'a>>), // attribute value with expressions, "p-{size}"
+    Unknown(char),               // unknown char
 
     EOF,
 }
 
+impl TokenKind<'_> {
+    /// Detach the token from the source it was lexed from.
+    pub fn into_owned(self) -> TokenKind<'static> {
+        fn own(text: Cow<'_, str>) -> Cow<'static, str> {
+            Cow::Owned(text.into_owned())
+        }
+
+        match self {
+            TokenKind::Lt => TokenKind::Lt,
+            TokenKind::Gt => TokenKind::Gt,
+            TokenKind::Slash => TokenKind::Slash,
+            TokenKind::Eq => TokenKind::Eq,
+            TokenKind::Name(text) => TokenKind::Name(own(text)),
+            TokenKind::Text(text) => TokenKind::Text(own(text)),
+            TokenKind::InnerText(text) => TokenKind::InnerText(own(text)),
+            TokenKind::StringLiteral(text) => TokenKind::StringLiteral(own(text)),
+            TokenKind::Comment(text) => TokenKind::Comment(own(text)),
+            TokenKind::RawText(text) => TokenKind::RawText(own(text)),
+            TokenKind::Expression(text) => TokenKind::Expression(own(text)),
+            TokenKind::Interpolated(parts) => TokenKind::Interpolated(
+                parts
+                    .into_iter()
+                    .map(|part| match part {
+                        StringPart::Literal(text) => StringPart::Literal(own(text)),
+                        StringPart::Expression(text, span) => StringPart::Expression(own(text), span),
+                    })
+                    .collect(),
+            ),
+            TokenKind::Unknown(c) => TokenKind::Unknown(c),
+            TokenKind::EOF => TokenKind::EOF,
+        }
+    }
+}
+
+/// A piece of an interpolated attribute value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart<'a> {
+    Literal(Cow<'a, str>),
+    /// The source between the braces, and the span of the whole `{...}`
+    Expression(Cow<'a, str>, Span),
+}
+
 #[derive(Debug, Clone)]
-pub struct Token {
-    pub kind: TokenKind,
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
     pub span: Span,
 }
+
+impl Token<'_> {
+    pub fn into_owned(self) -> Token<'static> {
+        Token { kind: self.kind.into_owned(), span: self.span }
+    }
+}
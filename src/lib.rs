@@ -1,11 +1,12 @@
+pub mod diagnostic;
 pub mod engine;
 pub mod error;
 pub mod lexer;
 pub mod parser;
-pub mod watchman;
 
+pub use diagnostic::{render_diagnostic, Diagnostic, Severity};
 pub use engine::*;
 pub use error::*;
 pub use lexer::Lexer;
 pub use parser::Parser;
-pub use watchman::*;
+pub use ::watchman::Watchman;
@@ -1,25 +1,131 @@
+use designtime_rs::engine::build::build;
+use designtime_rs::engine::components::ComponentRegistry;
+use designtime_rs::engine::router::{Router, DEFAULT_PAGES_DIR};
 use designtime_rs::engine::runtime::Runtime;
-use designtime_rs::{validate_and_load_workspace, Lexer, Parser, RenderLib, Watchman};
-use std::path::PathBuf;
+use designtime_rs::{validate_and_load_workspace, Diagnostic, Lexer, Parser, Watchman};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = color_eyre::install();
+    let json = std::env::args().any(|arg| arg == "--message-format=json");
+    let command = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
 
-    let file_path = "./src/examples/night01.page.dts";
-    let file_contents = std::fs::read_to_string(file_path)?;
+    let workspace = PathBuf::from("./designtime.json");
+    let config = match validate_and_load_workspace(&workspace) {
+        Ok(config) => config,
+        Err(errors) => {
+            for err in &errors {
+                report(&Diagnostic::from_error(err, Some("designtime.json")), "", json);
+            }
+            anyhow::bail!("{} error(s) in {}", errors.len(), workspace.display());
+        }
+    };
+    let components = match ComponentRegistry::load(&config, Path::new(".")) {
+        Ok(components) => components,
+        Err(errors) => {
+            for err in &errors {
+                report(&Diagnostic::from_error(&err.error, Some(&err.path)), &err.source, json);
+            }
+            anyhow::bail!("{} error(s) in components", errors.len());
+        }
+    };
+    let router = match Router::load(&config, Path::new(".")) {
+        Ok(router) => router,
+        Err(errors) => {
+            for err in &errors {
+                report(&Diagnostic::from_error(&err.error, Some(&err.path)), &err.source, json);
+            }
+            anyhow::bail!("{} error(s) in routes", errors.len());
+        }
+    };
+
+    if command.as_deref() == Some("build") {
+        let mut runtime = Runtime::new(config).with_components(components);
+        let summary = build(&mut runtime, &router, Path::new("."))?;
+        for warning in &summary.warnings {
+            report(warning, "", json);
+        }
+        for page in &summary.pages {
+            let output = page.output.as_ref().map_or("skipped".to_string(), |output| output.display().to_string());
+            eprintln!("{:>10.1?}  {} -> {}", page.duration, page.path, output);
+            for diagnostic in &page.diagnostics {
+                let source = diagnostic.file.as_ref().and_then(|file| std::fs::read_to_string(file).ok()).unwrap_or_default();
+                report(diagnostic, &source, json);
+            }
+        }
+        let built = summary.pages.iter().filter(|page| page.output.is_some()).count();
+        eprintln!("Built {} page(s) into {} in {:.1?}", built, summary.output_dir.display(), summary.duration);
+        let failed = summary.failed().count();
+        if failed > 0 {
+            anyhow::bail!("{} page(s) failed to build", failed);
+        }
+        return Ok(());
+    }
+
+    let Some(page) = router.resolve("/") else {
+        anyhow::bail!("No page for '/' in {}", config.routes.as_ref().and_then(|routes| routes.pages_dir.as_deref()).unwrap_or(DEFAULT_PAGES_DIR));
+    };
+    let file_path = page.route.file.display().to_string();
+    let file_contents = std::fs::read_to_string(&page.route.file)?;
     let lex = Lexer::new(&file_contents);
     let mut parse = Parser::new(lex);
-    let parsed_nodes = parse.parse()?;
+    let (parsed_nodes, errors) = parse.parse_document_recovering();
+    if !errors.is_empty() {
+        for err in &errors {
+            report(&Diagnostic::from_error(err, Some(&file_path)), &file_contents, json);
+        }
+        anyhow::bail!("{} parse error(s) in {}", errors.len(), file_path);
+    }
 
-    let workspace = PathBuf::from("./designtime.json");
-    let config = validate_and_load_workspace(workspace).expect("Failed to load workspace config");
-    let mut runtime = Runtime::new(config);
+    let mut runtime = Runtime::new(config).with_components(components);
 
-    runtime.run(vec![parsed_nodes]);
+    let data = page.data(&serde_json::Value::Object(Default::default()));
+    match runtime.process_page(parsed_nodes, &router.layouts(page.route), &data) {
+        // The debug dump isn't JSON, so it stays off stdout for tools reading diagnostics there
+        Ok(page) if !json => {
+            println!("DomNodes:\n{:#?}", page.nodes);
+            println!("CSS:\n{}", page.styles.generate_css());
+            for script in page.scripts {
+                println!("Script:\n{}", script);
+            }
+        }
+        Ok(_) => {}
+        Err(err) => {
+            // The error may be in a layout or component rather than the page itself
+            let source = match &err.file {
+                Some(file) if *file != file_path => std::fs::read_to_string(file).unwrap_or_default(),
+                _ => file_contents.clone(),
+            };
+            report(&Diagnostic::from_runtime_error(&err, Some(&file_path)), &source, json);
+        }
+    }
 
-    let render_lib = RenderLib::new(runtime);
-    let watchman = Watchman::new(render_lib);
+    // Report every problem in a changed file at once
+    let watchman = Watchman::new(move |path: &Path, source: &str| {
+        let (_, errors) = Parser::new(Lexer::new(source)).parse_document_recovering();
+        if errors.is_empty() {
+            return Ok(());
+        }
+        let file = path.display().to_string();
+        let diagnostics = errors.iter().map(|err| Diagnostic::from_error(err, Some(&file)));
+        if json {
+            diagnostics.for_each(|diagnostic| report(&diagnostic, source, json));
+            return Err(vec![format!("{} error(s) in {}", errors.len(), file)]);
+        }
+        Err(diagnostics.map(|diagnostic| diagnostic.render(source, false)).collect())
+    });
     watchman.run().await?;
     Ok(())
+}
+
+/// With `--message-format=json` each diagnostic is one JSON object per line on stdout,
+/// otherwise it is rendered for humans on stderr.
+fn report(diagnostic: &Diagnostic, source: &str, json: bool) {
+    if json {
+        println!("{}", diagnostic.to_json());
+    } else {
+        eprintln!("{}", diagnostic.render(source, std::io::stderr().is_terminal()));
+    }
 }
\ No newline at end of file
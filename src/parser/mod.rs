@@ -1,3 +1,4 @@
 pub mod parser;
+pub mod whitespace;
 
 pub use parser::Parser;
\ No newline at end of file
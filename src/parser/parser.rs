@@ -1,29 +1,181 @@
 use crate::{
-    lexer::{Token, TokenKind}, 
+    lexer::{StringPart, Token, TokenKind},
     Lexer, 
-    error::DesignTimeError
+    error::DesignTimeError,
+    Span,
 };
-use designtime_ast::Node;
+use crate::lexer::entities::{decode_entity, decode_escape};
+use crate::parser::whitespace::{collapse_whitespace, strip_leading_newline};
+use designtime_ast::{
+    is_component_tag, is_raw_text_element, is_void_element, preserves_whitespace, Attribute, AttributePart, AttributeValue, ClassName, IfBranch,
+    Node,
+};
+use std::collections::VecDeque;
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
-    current: Token,
+    current: Token<'a>,
+    /// Lookahead tokens following `current`, in order
+    peeked: VecDeque<Token<'a>>,
+    /// Span of the token consumed by the last `bump`
+    previous_span: Span,
+    keep_comments: bool,
+    /// When set, errors are collected in `errors` instead of aborting the parse
+    recovering: bool,
+    errors: Vec<DesignTimeError>,
+    /// Names of the elements currently being parsed, outermost first ("" for fragments,
+    /// "#if" and the like for blocks)
+    open_tags: Vec<String>,
+    /// How many of the open elements keep their whitespace, see `preserves_whitespace`
+    preserve_whitespace: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(mut lexer: Lexer<'a>) -> Self {
         let current = lexer.next_token();
-        Self { lexer, current }
+        Self {
+            lexer,
+            previous_span: current.span,
+            current,
+            peeked: VecDeque::new(),
+            keep_comments: false,
+            recovering: false,
+            errors: Vec::new(),
+            open_tags: Vec::new(),
+            preserve_whitespace: 0,
+        }
+    }
+
+    /// Keep comments as `Node::Comment` instead of dropping them, e.g. for a formatter.
+    pub fn keep_comments(mut self, keep: bool) -> Self {
+        self.keep_comments = keep;
+        self
     }
 
     fn bump(&mut self) {
-        self.current = self.lexer.next_token();
+        let next = match self.peeked.pop_front() {
+            Some(token) => token,
+            None => self.lexer.next_token(),
+        };
+        self.previous_span = std::mem::replace(&mut self.current, next).span;
+    }
+
+    /// Look at the token after `current` without consuming anything.
+    fn peek(&mut self) -> &Token<'a> {
+        self.peek_nth(0)
+    }
+
+    /// Look `n` tokens past the one after `current` without consuming anything.
+    fn peek_nth(&mut self, n: usize) -> &Token<'a> {
+        while self.peeked.len() <= n {
+            let token = self.lexer.next_token();
+            self.peeked.push_back(token);
+        }
+        &self.peeked[n]
+    }
+
+    /// In recovering mode, records `err` and lets parsing continue; otherwise hands it back.
+    fn recover(&mut self, err: DesignTimeError) -> Result<(), DesignTimeError> {
+        if self.recovering {
+            self.errors.push(err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Skips to the next tag boundary after an error: just past the current tag's '>',
+    /// or up to the next '<'.
+    fn synchronize(&mut self) {
+        loop {
+            match self.current.kind {
+                TokenKind::Lt | TokenKind::EOF => break,
+                TokenKind::Gt => {
+                    self.bump();
+                    break;
+                }
+                _ => self.bump(),
+            }
+        }
+    }
+
+    /// If `current` starts a closing tag, returns its name ("" for `</>`).
+    fn closing_tag_name(&mut self) -> Option<String> {
+        if self.current.kind != TokenKind::Lt || self.peek().kind != TokenKind::Slash {
+            return None;
+        }
+        match &self.peek_nth(1).kind {
+            TokenKind::Name(name) => Some(name.to_string()),
+            TokenKind::Gt => Some(String::new()),
+            _ => None,
+        }
     }
 
     pub fn parse(&mut self) -> Result<Node, DesignTimeError> {
+        while matches!(&self.current.kind, TokenKind::InnerText(text) if text.trim().is_empty()) {
+            self.bump();
+        }
         self.parse_element()
     }
 
+    /// Parses every top-level node in the source until EOF.
+    pub fn parse_document(&mut self) -> Result<Vec<Node>, DesignTimeError> {
+        let mut nodes = Vec::new();
+        loop {
+            match &self.current.kind {
+                TokenKind::EOF => break,
+                TokenKind::InnerText(text) => {
+                    nodes.push(Node::Text { content: text.to_string(), span: Some(self.current.span) });
+                    self.bump();
+                }
+                TokenKind::Expression(_) => match self.block_tag() {
+                    None => nodes.push(self.parse_expression()?),
+                    Some(tag) if tag.kind == BlockTagKind::Open => nodes.extend(self.parse_block_or_recover(tag)?),
+                    Some(tag) => {
+                        self.stray_block_tag(&tag)?;
+                    }
+                },
+                TokenKind::Comment(content) => {
+                    if self.keep_comments {
+                        nodes.push(Node::Comment { content: content.to_string(), span: Some(self.current.span) });
+                    }
+                    self.bump();
+                }
+                _ => nodes.extend(self.parse_element_or_recover()?),
+            }
+        }
+        Ok(collapse_whitespace(nodes, self.lexer.source()))
+    }
+
+    /// Parses the whole document without stopping at the first error.
+    /// After each problem the parser resynchronises at the next tag boundary, so the result
+    /// holds every node that could be built along with all errors found, in source order.
+    pub fn parse_document_recovering(&mut self) -> (Vec<Node>, Vec<DesignTimeError>) {
+        self.recovering = true;
+        let nodes = match self.parse_document() {
+            Ok(nodes) => nodes,
+            Err(err) => {
+                self.errors.push(err);
+                Vec::new()
+            }
+        };
+        (nodes, std::mem::take(&mut self.errors))
+    }
+
+    /// Parses an element; when recovering, a broken element is recorded and skipped instead.
+    fn parse_element_or_recover(&mut self) -> Result<Option<Node>, DesignTimeError> {
+        match self.parse_element() {
+            Ok(node) => Ok(Some(node)),
+            Err(err) => {
+                self.recover(err)?;
+                self.synchronize();
+                Ok(None)
+            }
+        }
+    }
+
     fn parse_element(&mut self) -> Result<Node, DesignTimeError> {
+        let start = self.current.span;
         if self.current.kind != TokenKind::Lt {
             return Err(DesignTimeError::ParserError {
                 span: self.current.span,
@@ -33,8 +185,22 @@ impl<'a> Parser<'a> {
         }
         self.bump();
 
+        // `<>` opens a fragment, closed by `</>`
+        if self.current.kind == TokenKind::Gt {
+            self.bump();
+            let children = self.parse_element_body("")?;
+            return Ok(Node::Fragment(children));
+        }
+
         let tag_name = match &self.current.kind {
-            TokenKind::Name(n) => n.clone(),
+            TokenKind::Name(n) => n.to_string(),
+            TokenKind::Slash => {
+                let close_name = match &self.peek().kind {
+                    TokenKind::Name(name) => name.to_string(),
+                    _ => String::new(),
+                };
+                return Err(unexpected_closing_tag(self.current.span, &close_name));
+            }
             _ => return Err(DesignTimeError::ParserError {
                 span: self.current.span,
                 message: format!("Expected tag name, got {:?}", self.current.kind),
@@ -43,38 +209,504 @@ impl<'a> Parser<'a> {
         };
         self.bump();
 
-        // Parse attributes before expecting '>'
+        // Parse attributes before expecting '>' or '/>'
         let (attributes, class_names) = self.parse_attributes()?;
 
-        if self.current.kind != TokenKind::Gt {
-            return Err(DesignTimeError::ParserError {
+        let self_closing = self.current.kind == TokenKind::Slash;
+        if self_closing {
+            self.bump();
+        }
+
+        let open_end = if self.current.kind == TokenKind::Gt {
+            let span = self.current.span;
+            self.bump();
+            span
+        } else {
+            let err = DesignTimeError::ParserError {
                 span: self.current.span,
                 message: format!("Expected '>', got {:?}", self.current.kind),
-                suggestion: Some("Close the opening tag with '>'".to_string()),
+                suggestion: Some(if self_closing {
+                    "Self-closing tags end with '/>'".to_string()
+                } else {
+                    "Close the opening tag with '>'".to_string()
+                }),
+            };
+            self.recover(err)?;
+            self.synchronize();
+            self.previous_span
+        };
+
+        // Components are never void or raw text, whatever their name, e.g. `<Input>` or `<Style>`
+        if is_component_tag(&tag_name) {
+            let children = if self_closing { Vec::new() } else { self.parse_element_body(&tag_name)? };
+            let end = if self_closing { open_end } else { self.previous_span };
+            return Ok(Node::Component { name: tag_name, attributes, children, body: None, span: Some(start.to(end)) });
+        }
+
+        if self_closing && is_raw_text_element(&tag_name) {
+            return Ok(Node::RawText { tag_name, attributes, content: String::new(), span: Some(start.to(open_end)) });
+        }
+
+        // `<tag />` and void elements like `<br>` have no children and no closing tag
+        if self_closing || is_void_element(&tag_name) {
+            return Ok(Node::Element {
+                tag_name,
+                attributes,
+                class_names,
+                children: Vec::new(),
+                key: None,
+                span: Some(start.to(open_end)),
             });
         }
+
+        if is_raw_text_element(&tag_name) {
+            return self.parse_raw_text_body(tag_name, attributes, start);
+        }
+
+        let preserve = preserves_whitespace(&tag_name, &attributes);
+        if preserve {
+            self.preserve_whitespace += 1;
+        }
+        let mut children = self.parse_element_body(&tag_name)?;
+        if preserve {
+            self.preserve_whitespace -= 1;
+        }
+        // Only `<pre>` and `<textarea>` drop a newline straight after the start tag, not `preserve-whitespace`
+        if preserves_whitespace(&tag_name, &[]) {
+            strip_leading_newline(&mut children);
+        }
+        let end = self.previous_span;
+
+        Ok(Node::Element { tag_name, attributes, class_names, children, key: None, span: Some(start.to(end)) })
+    }
+
+    /// Parses a `{...}` expression in text.
+    fn parse_expression(&mut self) -> Result<Node, DesignTimeError> {
+        let TokenKind::Expression(expression) = &self.current.kind else {
+            unreachable!("only called at an expression token");
+        };
+        let node = Node::Expression { expression: expression.to_string(), span: Some(self.current.span) };
+        self.check_expression_closed(self.current.span)?;
         self.bump();
+        Ok(node)
+    }
+
+    /// The lexer lets an unterminated `{` run to the end of input; report it here.
+    fn check_expression_closed(&mut self, span: Span) -> Result<(), DesignTimeError> {
+        if self.lexer.source()[..span.end_offset].ends_with('}') {
+            return Ok(());
+        }
+        self.recover(DesignTimeError::ParserError {
+            span,
+            message: "Unterminated expression: missing '}'".to_string(),
+            suggestion: Some("Close the expression with '}', or write a literal brace as \\{ or &lbrace;".to_string()),
+        })
+    }
+
+    /// If `current` is a `{#...}`, `{:...}` or `{/...}` block tag, splits it into its parts.
+    fn block_tag(&self) -> Option<BlockTag> {
+        let TokenKind::Expression(text) = &self.current.kind else {
+            return None;
+        };
+        let kind = match text.chars().next()? {
+            '#' => BlockTagKind::Open,
+            ':' => BlockTagKind::Next,
+            '/' => BlockTagKind::Close,
+            _ => return None,
+        };
+
+        let body = &text[1..];
+        let name_len = body.find(|c: char| !c.is_alphanumeric()).unwrap_or(body.len());
+        let mut name = body[..name_len].to_string();
+        let mut rest = &body[name_len..];
+        // `{:else if cond}` continues with another condition
+        if kind == BlockTagKind::Next
+            && name == "else"
+            && let Some(after) = rest.trim_start().strip_prefix("if")
+            && (after.is_empty() || after.starts_with(char::is_whitespace))
+        {
+            name.push_str(" if");
+            rest = after;
+        }
 
+        let argument = rest.trim();
+        // The token's text starts just after its '{'
+        let argument_start = self.current.span.start_offset + 1 + (text.len() - rest.trim_start().len());
+        Some(BlockTag {
+            kind,
+            name,
+            argument: argument.to_string(),
+            argument_span: self.span_between(self.current.span, argument_start, argument_start + argument.len()),
+            span: self.current.span,
+        })
+    }
+
+    /// The span of `source[start..end]`, worked out by walking forward from the start of `from`.
+    fn span_between(&self, from: Span, start: usize, end: usize) -> Span {
+        let source = self.lexer.source();
+        let advance = |(mut line, mut column): (usize, usize), text: &str| {
+            for c in text.chars() {
+                if c == '\n' {
+                    line += 1;
+                    column = 0;
+                } else {
+                    column += 1;
+                }
+            }
+            (line, column)
+        };
+        let (start_line, start_column) = advance((from.start_line, from.start_column), &source[from.start_offset..start]);
+        let (end_line, end_column) = advance((start_line, start_column), &source[start..end]);
+        Span { start_line, start_column, end_line, end_column, start_offset: start, end_offset: end }
+    }
+
+    /// Parses a block; when recovering, a broken block tag is recorded and skipped instead.
+    fn parse_block_or_recover(&mut self, open: BlockTag) -> Result<Option<Node>, DesignTimeError> {
+        match self.parse_block(open) {
+            Ok(node) => Ok(Some(node)),
+            Err(err) => {
+                self.recover(err)?;
+                self.bump();
+                Ok(None)
+            }
+        }
+    }
+
+    fn parse_block(&mut self, open: BlockTag) -> Result<Node, DesignTimeError> {
+        self.check_expression_closed(open.span)?;
+        match open.name.as_str() {
+            "if" => self.parse_if_block(open),
+            "each" => self.parse_each_block(open),
+            _ => Err(DesignTimeError::ParserError {
+                span: open.span,
+                message: format!("Unknown block {{#{}}}", open.name),
+                suggestion: Some("Use {#if condition} ... {/if} or {#each items as item} ... {/each}".to_string()),
+            }),
+        }
+    }
+
+    /// Parses `{#if cond}...{:else if cond}...{:else}...{/if}`, starting at the `{#if}`.
+    fn parse_if_block(&mut self, open: BlockTag) -> Result<Node, DesignTimeError> {
+        let start = open.span;
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        // `None` once `{:else}` has been seen
+        let mut condition = Some(block_condition(open)?);
+        self.bump();
+
+        let end = loop {
+            let children = self.parse_block_body("if")?;
+            match condition.take() {
+                Some((condition, span)) => branches.push(IfBranch { condition, condition_span: Some(span), children }),
+                None => otherwise = Some(children),
+            }
+
+            let Some(tag) = self.block_tag() else {
+                break self.unclosed_block("if")?;
+            };
+            self.check_expression_closed(tag.span)?;
+            match (tag.kind, tag.name.as_str()) {
+                (BlockTagKind::Next, "else if") if otherwise.is_none() => {
+                    condition = Some(block_condition(tag)?);
+                    self.bump();
+                }
+                (BlockTagKind::Next, "else") if otherwise.is_none() && tag.argument.is_empty() => self.bump(),
+                (BlockTagKind::Close, "if") => {
+                    self.bump();
+                    break tag.span;
+                }
+                _ => {
+                    if self.unexpected_block_tag(&tag, "if")? {
+                        break tag.span;
+                    }
+                }
+            }
+        };
+
+        Ok(Node::If { branches, otherwise, span: Some(start.to(end)) })
+    }
+
+    /// Parses `{#each items as item, index (key)}...{:else}...{/each}`, starting at the `{#each}`.
+    fn parse_each_block(&mut self, open: BlockTag) -> Result<Node, DesignTimeError> {
+        let start = open.span;
+        let header = self.each_header(&open)?;
+        self.bump();
+
+        let children = self.parse_block_body("each")?;
+        let mut otherwise = None;
+        let end = loop {
+            let Some(tag) = self.block_tag() else {
+                break self.unclosed_block("each")?;
+            };
+            self.check_expression_closed(tag.span)?;
+            match (tag.kind, tag.name.as_str()) {
+                (BlockTagKind::Next, "else") if otherwise.is_none() && tag.argument.is_empty() => {
+                    self.bump();
+                    otherwise = Some(self.parse_block_body("each")?);
+                }
+                (BlockTagKind::Close, "each") => {
+                    self.bump();
+                    break tag.span;
+                }
+                _ => {
+                    if self.unexpected_block_tag(&tag, "each")? {
+                        break tag.span;
+                    }
+                }
+            }
+        };
+
+        Ok(Node::Each {
+            items: header.items.0,
+            items_span: Some(header.items.1),
+            item: header.item,
+            index: header.index,
+            key: header.key.as_ref().map(|(key, _)| key.clone()),
+            key_span: header.key.map(|(_, span)| span),
+            children,
+            otherwise,
+            span: Some(start.to(end)),
+        })
+    }
+
+    /// Splits `items as item, index (key)` from an `{#each}`; the index and key are optional.
+    fn each_header(&self, open: &BlockTag) -> Result<EachHeader, DesignTimeError> {
+        let invalid = |message: &str| DesignTimeError::ParserError {
+            span: open.span,
+            message: message.to_string(),
+            suggestion: Some("Write it as {#each items as item}, {#each items as item, i} or {#each items as item, i (item.id)}".to_string()),
+        };
+        let argument = open.argument.as_str();
+        let base = open.argument_span.start_offset;
+        // Spans of parts of the argument, given as byte ranges within it
+        let span_of = |text: &str| {
+            let start = base + (text.as_ptr() as usize - argument.as_ptr() as usize);
+            self.span_between(open.argument_span, start, start + text.len())
+        };
+
+        let Some((items, binding)) = argument.split_once(" as ") else {
+            return Err(invalid("Expected 'as' in {#each}"));
+        };
+        let items = items.trim_end();
+        if items.is_empty() {
+            return Err(invalid("Missing list in {#each}"));
+        }
+
+        let mut binding = binding.trim();
+        let mut key = None;
+        if let Some(open_paren) = binding.find('(') {
+            let Some(inner) = binding[open_paren + 1..].strip_suffix(')') else {
+                return Err(invalid("Expected ')' to end the key of {#each}"));
+            };
+            let inner = inner.trim();
+            if inner.is_empty() {
+                return Err(invalid("Missing key expression in {#each}"));
+            }
+            key = Some((inner.to_string(), span_of(inner)));
+            binding = binding[..open_paren].trim_end();
+        }
+
+        let (item, index) = match binding.split_once(',') {
+            Some((item, index)) => (item.trim(), Some(index.trim())),
+            None => (binding, None),
+        };
+        let is_identifier = |name: &str| {
+            name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        };
+        if !is_identifier(item) || !index.is_none_or(is_identifier) {
+            return Err(invalid("Expected a name for the item, and optionally one for the index, after 'as'"));
+        }
+
+        Ok(EachHeader {
+            items: (items.to_string(), span_of(items)),
+            item: item.to_string(),
+            index: index.map(str::to_string),
+            key,
+        })
+    }
+
+    /// A block body stopped without its closing tag: at the end of input or, when recovering,
+    /// at an ancestor's closing tag. When recovering the block ends where its content did.
+    fn unclosed_block(&mut self, name: &str) -> Result<Span, DesignTimeError> {
+        if self.recovering {
+            // The missing closing tag has been reported by `parse_children`
+            return Ok(self.previous_span);
+        }
+        Err(DesignTimeError::ParserError {
+            span: self.current.span,
+            message: format!("Unclosed {{#{}}} block", name),
+            suggestion: Some(format!("Expected {{/{}}}", name)),
+        })
+    }
+
+    /// Reports a `{:...}` or `{/...}` that doesn't fit the `{#name}` block and skips it.
+    /// Returns true when the tag was a closing one, which ends the block when recovering.
+    fn unexpected_block_tag(&mut self, tag: &BlockTag, name: &str) -> Result<bool, DesignTimeError> {
+        let suggestion = match name {
+            "if" => "An {#if} block continues with {:else if condition} or {:else}, and ends with {/if}",
+            _ => "An {#each} block can have one {:else} for an empty list, and ends with {/each}",
+        };
+        self.recover(DesignTimeError::ParserError {
+            span: tag.span,
+            message: format!("Unexpected {} in {{#{}}} block", &self.lexer.source()[tag.span.start_offset..tag.span.end_offset], name),
+            suggestion: Some(suggestion.to_string()),
+        })?;
+        self.bump();
+        Ok(tag.kind == BlockTagKind::Close)
+    }
+
+    /// Parses the nodes of one branch of a block, up to its next `{:...}` or `{/...}` tag.
+    fn parse_block_body(&mut self, name: &str) -> Result<Vec<Node>, DesignTimeError> {
+        let marker = format!("#{}", name);
+        self.open_tags.push(marker.clone());
+        let children = self.parse_children(&marker)?;
+        self.open_tags.pop();
+        Ok(children)
+    }
+
+    /// Reports a `{:...}` or `{/...}` that doesn't belong to the innermost open block and skips it.
+    /// When recovering inside a block, returns true so the elements left open in it get closed.
+    fn stray_block_tag(&mut self, tag: &BlockTag) -> Result<bool, DesignTimeError> {
+        let text = &self.lexer.source()[tag.span.start_offset..tag.span.end_offset];
+        let in_block = self.open_tags.iter().any(|name| is_block(name));
+        let err = match self.open_tags.last() {
+            Some(name) if in_block => DesignTimeError::ParserError {
+                span: tag.span,
+                message: format!("Unexpected {}: {} is still open", text, opener(name)),
+                suggestion: Some(format!("Close {} before {}", opener(name), text)),
+            },
+            _ => DesignTimeError::ParserError {
+                span: tag.span,
+                message: format!("Unexpected {} outside of a block", text),
+                suggestion: Some("Remove it or add the {#if ...} or {#each ...} it belongs to".to_string()),
+            },
+        };
+        self.recover(err)?;
+        if in_block {
+            return Ok(true);
+        }
+        self.bump();
+        Ok(false)
+    }
+
+    /// Parses the verbatim content of a `<script>` or `<style>` and its closing tag.
+    fn parse_raw_text_body(&mut self, tag_name: String, attributes: Vec<Attribute>, start: Span) -> Result<Node, DesignTimeError> {
+        let content = match &self.current.kind {
+            TokenKind::RawText(content) => {
+                let content = content.to_string();
+                self.bump();
+                content
+            }
+            _ => String::new(),
+        };
+
+        let end = match self.parse_closing_tag(&tag_name) {
+            Ok(end) => end,
+            Err(err) => {
+                self.recover(err)?;
+                self.previous_span
+            }
+        };
+
+        Ok(Node::RawText { tag_name, attributes, content, span: Some(start.to(end)) })
+    }
+
+    /// Parses an element's children and its closing tag.
+    /// When recovering, an element left open by an error is closed where its content ends.
+    fn parse_element_body(&mut self, tag_name: &str) -> Result<Vec<Node>, DesignTimeError> {
+        self.open_tags.push(tag_name.to_string());
+        let children = self.parse_children(tag_name)?;
+        self.open_tags.pop();
+
+        if !self.recovering || self.closing_tag_name().is_some_and(|name| closes(tag_name, &name)) {
+            self.parse_closing_tag(tag_name)?;
+        }
+        Ok(children)
+    }
+
+    /// Parses text and nested elements in source order until the closing '</'.
+    /// An empty `tag_name` means the children belong to a fragment.
+    fn parse_children(&mut self, tag_name: &str) -> Result<Vec<Node>, DesignTimeError> {
         let mut children = Vec::new();
-        while self.current.kind != TokenKind::Lt {
+        loop {
+            let at_closing_tag = self.current.kind == TokenKind::Lt && self.peek().kind == TokenKind::Slash;
             match &self.current.kind {
                 TokenKind::InnerText(text) => {
-                    children.push(Node::Text(text.clone()));
+                    children.push(Node::Text { content: text.to_string(), span: Some(self.current.span) });
+                    self.bump();
+                }
+                TokenKind::Expression(_) => match self.block_tag() {
+                    None => children.push(self.parse_expression()?),
+                    Some(tag) if tag.kind == BlockTagKind::Open => children.extend(self.parse_block_or_recover(tag)?),
+                    // `{:else}` or `{/if}` ends the body of the block being parsed
+                    Some(_) if is_block(tag_name) => break,
+                    Some(tag) => {
+                        if self.stray_block_tag(&tag)? {
+                            break;
+                        }
+                    }
+                },
+                TokenKind::Comment(content) => {
+                    if self.keep_comments {
+                        children.push(Node::Comment { content: content.to_string(), span: Some(self.current.span) });
+                    }
+                    self.bump();
+                }
+                TokenKind::Lt if at_closing_tag => {
+                    if !self.recovering {
+                        break;
+                    }
+                    match self.closing_tag_name() {
+                        Some(name) if closes(tag_name, &name) => break,
+                        // Closes an ancestor, so this element was never closed
+                        Some(name) if self.open_tags.iter().any(|open| closes(open, &name)) => {
+                            let span = self.peek_nth(1).span;
+                            self.recover(DesignTimeError::ParserError {
+                                span,
+                                message: format!("Mismatched closing tag: expected {}, got </{}>", closer(tag_name), name),
+                                suggestion: Some(format!("Close {} before closing its parent", opener(tag_name))),
+                            })?;
+                            break;
+                        }
+                        // Closes nothing that is open: skip it
+                        close_name => {
+                            self.bump();
+                            let err = unexpected_closing_tag(self.current.span, &close_name.unwrap_or_default());
+                            self.recover(err)?;
+                            self.synchronize();
+                        }
+                    }
+                }
+                TokenKind::Lt => children.extend(self.parse_element_or_recover()?),
+                TokenKind::EOF => {
+                    self.recover(DesignTimeError::ParserError {
+                        span: self.current.span,
+                        message: "Unexpected end of file".to_string(),
+                        suggestion: Some(format!("Expected closing tag {}", closer(tag_name))),
+                    })?;
+                    break;
+                }
+                other => {
+                    let err = DesignTimeError::ParserError {
+                        span: self.current.span,
+                        message: format!("Unexpected token: {:?}", other),
+                        suggestion: Some("Expected text content, a child element or closing tag".to_string()),
+                    };
+                    self.recover(err)?;
                     self.bump();
                 }
-                TokenKind::EOF => return Err(DesignTimeError::ParserError {
-                    span: self.current.span,
-                    message: "Unexpected end of file".to_string(),
-                    suggestion: Some(format!("Expected closing tag </{}>", tag_name)),
-                }),
-                other => return Err(DesignTimeError::ParserError {
-                    span: self.current.span,
-                    message: format!("Unexpected token: {:?}", other),
-                    suggestion: Some("Expected text content or closing tag".to_string()),
-                }),
             }
         }
 
+        if self.preserve_whitespace == 0 {
+            children = collapse_whitespace(children, self.lexer.source());
+        }
+        Ok(children)
+    }
+
+    /// Consumes `</tag_name>`, or `</>` when `tag_name` is empty, returning the span of its final '>'.
+    fn parse_closing_tag(&mut self, tag_name: &str) -> Result<Span, DesignTimeError> {
         // Now at '<', check for closing tag
         self.bump();
         if self.current.kind != TokenKind::Slash {
@@ -87,7 +719,8 @@ impl<'a> Parser<'a> {
         self.bump();
 
         match &self.current.kind {
-            TokenKind::Name(close_name) if *close_name == tag_name => (),
+            TokenKind::Name(close_name) if closes(tag_name, close_name) => self.bump(),
+            TokenKind::Gt if tag_name.is_empty() => (),
             TokenKind::Name(close_name) => {
                 return Err(DesignTimeError::ParserError {
                     span: self.current.span,
@@ -95,76 +728,690 @@ impl<'a> Parser<'a> {
                     suggestion: Some(format!("Change the closing tag to match the opening tag: </{}>", tag_name)),
                 })
             }
+            TokenKind::Gt => {
+                return Err(DesignTimeError::ParserError {
+                    span: self.current.span,
+                    message: format!("Mismatched closing tag: expected </{}>, got </>", tag_name),
+                    suggestion: Some("Use '</>' only to close a fragment opened with '<>'".to_string()),
+                })
+            }
             other => return Err(DesignTimeError::ParserError {
                 span: self.current.span,
                 message: format!("Expected closing tag name, got {:?}", other),
                 suggestion: Some(format!("Expected the tag name '{}' after '</'", tag_name)),
             }),
         }
-        self.bump();
 
         if self.current.kind != TokenKind::Gt {
-            return Err(DesignTimeError::ParserError {
+            self.recover(DesignTimeError::ParserError {
                 span: self.current.span,
                 message: format!("Expected '>' to close tag, got {:?}", self.current.kind),
                 suggestion: Some("Close the closing tag with '>'".to_string()),
-            });
+            })?;
+            self.synchronize();
+            return Ok(self.previous_span);
         }
+        let end = self.current.span;
         self.bump();
 
-        Ok(Node::Element { tag_name, attributes, class_names, children })
+        Ok(end)
     }
 
-    fn parse_attributes(&mut self) -> Result<(Vec<(String, String)>, Vec<String>), DesignTimeError> {
+    fn parse_attributes(&mut self) -> Result<(Vec<Attribute>, Vec<ClassName>), DesignTimeError> {
         let mut attributes = Vec::new();
         let mut class_names = Vec::new();
 
-        while self.current.kind != TokenKind::Gt {
+        while self.current.kind != TokenKind::Gt && self.current.kind != TokenKind::Slash {
             // Expect attribute name
             let attr_name = match &self.current.kind {
-                TokenKind::Name(name) => name.clone(),
-                _ => return Err(DesignTimeError::ParserError {
-                    span: self.current.span,
-                    message: format!("Expected attribute name, got {:?}", self.current.kind),
-                    suggestion: Some("Attribute names should be valid identifiers (e.g., class, id, style)".to_string()),
-                }),
+                TokenKind::Name(name) => name.to_string(),
+                _ => {
+                    self.recover(DesignTimeError::ParserError {
+                        span: self.current.span,
+                        message: format!("Expected attribute name, got {:?}", self.current.kind),
+                        suggestion: Some("Attribute names should be valid identifiers (e.g., class, id, style)".to_string()),
+                    })?;
+                    // Skip the bad token and carry on with the rest of the tag
+                    if self.current.kind == TokenKind::EOF {
+                        break;
+                    }
+                    self.bump();
+                    continue;
+                }
             };
+            let name_span = self.current.span;
             self.bump();
 
-            // Expect '='
+            // Without '=' this is a boolean attribute, e.g. <input disabled>
             if self.current.kind != TokenKind::Eq {
-                return Err(DesignTimeError::ParserError {
-                    span: self.current.span,
-                    message: format!("Expected '=', got {:?}", self.current.kind),
-                    suggestion: Some("Attributes should be in the format: name=\"value\"".to_string()),
-                });
+                attributes.push(Attribute { name: attr_name, value: AttributeValue::Boolean, span: Some(name_span) });
+                continue;
             }
             self.bump();
 
-            // Expect attribute value as string literal
+            // Expect attribute value as a quoted or unquoted string literal, maybe with expressions
             let attr_value = match &self.current.kind {
-                TokenKind::StringLiteral(value) => value.clone(),
-                _ => return Err(DesignTimeError::ParserError {
-                    span: self.current.span,
-                    message: format!("Expected string literal, got {:?}", self.current.kind),
-                    suggestion: Some("Attribute values should be quoted strings (e.g., \"value\")".to_string()),
-                }),
+                TokenKind::StringLiteral(value) => AttributeValue::Literal(value.to_string()),
+                TokenKind::Interpolated(parts) => {
+                    let parts: Vec<AttributePart> = parts
+                        .iter()
+                        .map(|part| match part {
+                            StringPart::Literal(text) => AttributePart::Literal(text.to_string()),
+                            StringPart::Expression(expression, span) => AttributePart::Expression {
+                                expression: expression.to_string(),
+                                span: Some(*span),
+                            },
+                        })
+                        .collect();
+                    for part in &parts {
+                        if let AttributePart::Expression { span: Some(span), .. } = part {
+                            self.check_expression_closed(*span)?;
+                        }
+                    }
+                    AttributeValue::Interpolated(parts)
+                }
+                _ => {
+                    self.recover(DesignTimeError::ParserError {
+                        span: self.current.span,
+                        message: format!("Expected attribute value, got {:?}", self.current.kind),
+                        suggestion: Some("Attributes should be in the format: name=\"value\", name=value or name".to_string()),
+                    })?;
+                    if !matches!(self.current.kind, TokenKind::Gt | TokenKind::Slash | TokenKind::EOF) {
+                        self.bump();
+                    }
+                    continue;
+                }
             };
+            let value_span = self.current.span;
             self.bump();
 
+            // Special handling for class attribute; interpolated classes are only known at runtime
+            if attr_name == "class" && matches!(attr_value, AttributeValue::Literal(_)) {
+                class_names = self.split_class_names(value_span);
+            }
+
             // Store all attributes in the attributes vector
-            attributes.push((attr_name.clone(), attr_value.clone()));
+            attributes.push(Attribute {
+                name: attr_name,
+                value: attr_value,
+                span: Some(name_span.to(value_span)),
+            });
+        }
+
+        Ok((attributes, class_names))
+    }
+
+    /// Splits a class string by whitespace, giving each class the span of its text inside the value token.
+    /// Names are decoded from the raw source class by class, so entities don't shift the spans after them.
+    fn split_class_names(&self, value_span: Span) -> Vec<ClassName> {
+        let token = &self.lexer.source()[value_span.start_offset..value_span.end_offset];
+        let quote = token.chars().next().filter(|c| matches!(c, '"' | '\''));
+
+        let mut pos = Span { end_line: 0, end_column: 0, end_offset: 0, ..value_span };
+        let mut raw = token;
+        if let Some(quote) = quote {
+            raw = token[1..].strip_suffix(quote).unwrap_or(&token[1..]);
+            pos.start_column += 1;
+            pos.start_offset += 1;
+        }
+        let until = |start: Span, pos: Span| Span { end_line: pos.start_line, end_column: pos.start_column, end_offset: pos.start_offset, ..start };
+
+        let mut class_names = Vec::new();
+        let mut current: Option<(String, Span)> = None;
+        while let Some(c) = raw.chars().next() {
+            let (decoded, len) = match c {
+                '&' => decode_entity(raw),
+                '\\' => raw[1..].chars().next().and_then(|next| decode_escape(next, quote)).map(|decoded| (decoded, 2)),
+                _ => None,
+            }
+            .unwrap_or((c, c.len_utf8()));
+
+            if decoded.is_whitespace() {
+                if let Some((name, start)) = current.take() {
+                    class_names.push(ClassName { name, span: Some(until(start, pos)) });
+                }
+            } else {
+                current.get_or_insert_with(|| (String::new(), pos)).0.push(decoded);
+            }
 
-            // Special handling for class attribute
-            if attr_name == "class" {
-                // Split class string by whitespace into Vec<String>
-                class_names = attr_value
-                    .split_whitespace()
-                    .map(|s| s.to_string())
-                    .collect();
+            for c in raw[..len].chars() {
+                pos.start_offset += c.len_utf8();
+                if c == '\n' {
+                    pos.start_line += 1;
+                    pos.start_column = 0;
+                } else {
+                    pos.start_column += 1;
+                }
             }
+            raw = &raw[len..];
         }
+        if let Some((name, start)) = current {
+            class_names.push(ClassName { name, span: Some(until(start, pos)) });
+        }
+        class_names
+    }
+}
 
-        Ok((attributes, class_names))
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlockTagKind {
+    /// `{#if cond}` or `{#each ...}`
+    Open,
+    /// `{:else if cond}` or `{:else}`
+    Next,
+    /// `{/if}` or `{/each}`
+    Close,
+}
+
+/// A `{#...}`, `{:...}` or `{/...}` expression token, split into its parts.
+struct BlockTag {
+    kind: BlockTagKind,
+    /// `if`, `else`, `else if` and so on
+    name: String,
+    /// What follows the name, such as an `{#if}`'s condition
+    argument: String,
+    argument_span: Span,
+    span: Span,
+}
+
+/// The parts of an `{#each}` tag, with the spans of its expressions.
+struct EachHeader {
+    items: (String, Span),
+    item: String,
+    index: Option<String>,
+    key: Option<(String, Span)>,
+}
+
+/// The condition of an `{#if}` or `{:else if}`, with its span.
+fn block_condition(tag: BlockTag) -> Result<(String, Span), DesignTimeError> {
+    if tag.argument.is_empty() {
+        return Err(DesignTimeError::ParserError {
+            span: tag.span,
+            message: format!("Missing condition in {{{}{}}}", if tag.kind == BlockTagKind::Open { "#" } else { ":" }, tag.name),
+            suggestion: Some(format!("Write the condition after '{}', e.g. {{#if user.admin}}", tag.name)),
+        });
+    }
+    Ok((tag.argument, tag.argument_span))
+}
+
+/// Whether an `open_tags` entry is a block rather than an element.
+fn is_block(name: &str) -> bool {
+    name.starts_with('#')
+}
+
+/// How an `open_tags` entry is written when opened: `<div>`, `<>` or `{#if}`.
+fn opener(name: &str) -> String {
+    match name.strip_prefix('#') {
+        Some(block) => format!("{{#{}}}", block),
+        None => format!("<{}>", name),
+    }
+}
+
+/// Whether `</close_name>` closes `tag_name`. HTML tag names are case-insensitive,
+/// component names are not.
+fn closes(tag_name: &str, close_name: &str) -> bool {
+    if is_component_tag(tag_name) {
+        tag_name == close_name
+    } else {
+        tag_name.eq_ignore_ascii_case(close_name)
+    }
+}
+
+/// How an `open_tags` entry is closed: `</div>`, `</>` or `{/if}`.
+fn closer(name: &str) -> String {
+    match name.strip_prefix('#') {
+        Some(block) => format!("{{/{}}}", block),
+        None => format!("</{}>", name),
+    }
+}
+
+fn unexpected_closing_tag(span: Span, name: &str) -> DesignTimeError {
+    DesignTimeError::ParserError {
+        span,
+        message: format!("Unexpected closing tag </{}>", name),
+        suggestion: Some("Remove the closing tag or add a matching opening tag".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Node, DesignTimeError> {
+        Parser::new(Lexer::new(source)).parse()
+    }
+
+    fn parse_document(source: &str) -> Result<Vec<Node>, DesignTimeError> {
+        Parser::new(Lexer::new(source)).parse_document()
+    }
+
+    #[test]
+    fn test_parse_nested_elements() {
+        let node = parse("<div><p>hi</p><span>there</span></div>").unwrap();
+
+        let Node::Element { tag_name, children, .. } = node else {
+            panic!("Expected element node");
+        };
+        assert_eq!(tag_name, "div");
+        assert_eq!(children.len(), 2);
+
+        match &children[0] {
+            Node::Element { tag_name, children, .. } => {
+                assert_eq!(tag_name, "p");
+                assert!(matches!(&children[..], [Node::Text { content, .. }] if content == "hi"));
+            }
+            _ => panic!("Expected <p> element"),
+        }
+        assert!(matches!(&children[1], Node::Element { tag_name, .. } if tag_name == "span"));
+    }
+
+    #[test]
+    fn test_parse_text_and_elements_in_source_order() {
+        let node = parse("<p>Hello <b>world</b> again</p>").unwrap();
+
+        let Node::Element { children, .. } = node else {
+            panic!("Expected element node");
+        };
+        assert_eq!(children.len(), 3);
+        assert!(matches!(&children[0], Node::Text { content, .. } if content == "Hello "));
+        assert!(matches!(&children[1], Node::Element { tag_name, .. } if tag_name == "b"));
+        assert!(matches!(&children[2], Node::Text { content, .. } if content == " again"));
+    }
+
+    #[test]
+    fn test_parse_mismatched_nested_closing_tag() {
+        let err = parse("<div><p>hi</span></div>").unwrap_err();
+
+        match err {
+            DesignTimeError::ParserError { message, .. } => {
+                assert_eq!(message, "Mismatched closing tag: expected </p>, got </span>");
+            }
+            other => panic!("Expected parser error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_document_multiple_roots() {
+        let nodes = parse_document("<header>Top</header><main>Body</main><footer>End</footer>").unwrap();
+
+        let tags: Vec<&str> = nodes.iter()
+            .map(|n| match n {
+                Node::Element { tag_name, .. } => tag_name.as_str(),
+                _ => panic!("Expected element node"),
+            })
+            .collect();
+        assert_eq!(tags, ["header", "main", "footer"]);
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let nodes = parse_document("<><p>a</p><p>b</p></>").unwrap();
+
+        assert_eq!(nodes.len(), 1);
+        match &nodes[0] {
+            Node::Fragment(children) => assert_eq!(children.len(), 2),
+            other => panic!("Expected fragment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_fragment_closed_by_element_tag() {
+        let err = parse_document("<><p>a</p></div>").unwrap_err();
+
+        match err {
+            DesignTimeError::ParserError { message, .. } => {
+                assert_eq!(message, "Mismatched closing tag: expected </>, got </div>");
+            }
+            other => panic!("Expected parser error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_self_closing_element() {
+        let node = parse(r#"<div><img src="a.png" /><span/></div>"#).unwrap();
+
+        let Node::Element { children, .. } = node else {
+            panic!("Expected element node");
+        };
+        assert_eq!(children.len(), 2);
+        match &children[0] {
+            Node::Element { tag_name, attributes, children, .. } => {
+                assert_eq!(tag_name, "img");
+                assert_eq!(attributes.len(), 1);
+                assert_eq!(attributes[0].name, "src");
+                assert_eq!(attributes[0].value, AttributeValue::Literal("a.png".to_string()));
+                assert!(children.is_empty());
+            }
+            _ => panic!("Expected <img> element"),
+        }
+        assert!(matches!(&children[1], Node::Element { tag_name, children, .. } if tag_name == "span" && children.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_void_elements_without_closing_tag() {
+        let node = parse(r#"<p>one<br>two<hr><input type="text"></p>"#).unwrap();
+
+        let Node::Element { children, .. } = node else {
+            panic!("Expected element node");
+        };
+        let tags: Vec<&str> = children.iter()
+            .filter_map(|n| match n {
+                Node::Element { tag_name, .. } => Some(tag_name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tags, ["br", "hr", "input"]);
+        assert_eq!(children.len(), 5);
+    }
+
+    #[test]
+    fn test_parse_boolean_and_unquoted_attributes() {
+        let node = parse(r#"<td colspan=2 hidden data-x="" href=/a/b/></td>"#).unwrap();
+
+        let Node::Element { attributes, .. } = node else {
+            panic!("Expected element node");
+        };
+        let attributes: Vec<(&str, &AttributeValue)> = attributes.iter()
+            .map(|a| (a.name.as_str(), &a.value))
+            .collect();
+        assert_eq!(attributes, [
+            ("colspan", &AttributeValue::Literal("2".to_string())),
+            ("hidden", &AttributeValue::Boolean),
+            ("data-x", &AttributeValue::Literal(String::new())),
+            ("href", &AttributeValue::Literal("/a/b/".to_string())),
+        ]);
+
+        // `/>` only self-closes after whitespace; straight after an unquoted value it is part of it
+        let Node::Element { attributes, children, .. } = parse("<a href=/docs/>Docs</a>").unwrap() else {
+            panic!("Expected element node");
+        };
+        assert_eq!(attributes[0].value, AttributeValue::Literal("/docs/".to_string()));
+        assert!(matches!(&children[..], [Node::Text { content, .. }] if content == "Docs"));
+        let Node::Element { attributes, children, .. } = parse("<br class=x />").unwrap() else {
+            panic!("Expected element node");
+        };
+        assert_eq!(attributes[0].value, AttributeValue::Literal("x".to_string()));
+        assert!(children.is_empty());
+    }
+
+    #[test]
+    fn test_node_spans_point_into_source() {
+        let source = "<div id=\"main\" class=\"flex\n  p-4\">\n  Hi<br>\n</div>";
+        let node = parse(source).unwrap();
+
+        let Node::Element { attributes, class_names, children, span, .. } = node else {
+            panic!("Expected element node");
+        };
+        let slice = |span: Option<Span>| {
+            let span = span.expect("span should be set");
+            &source[span.start_offset..span.end_offset]
+        };
+
+        assert_eq!(slice(span), source);
+        assert_eq!(slice(attributes[0].span), "id=\"main\"");
+        assert_eq!(slice(class_names[0].span), "flex");
+        assert_eq!(slice(class_names[1].span), "p-4");
+        let p4 = class_names[1].span.unwrap();
+        assert_eq!((p4.start_line, p4.start_column, p4.end_column), (2, 2, 5));
+        assert_eq!(slice(children[0].span()), "Hi");
+        assert_eq!(slice(children[1].span()), "<br>");
+    }
+
+    #[test]
+    fn test_class_spans_with_entities() {
+        let source = "<div class=\"a&amp;b c&#32;d\">x</div>";
+        let Node::Element { class_names, .. } = parse(source).unwrap() else {
+            panic!("Expected element node");
+        };
+
+        let classes: Vec<_> = class_names
+            .iter()
+            .map(|class| {
+                let span = class.span.expect("span should be set");
+                (class.name.as_str(), &source[span.start_offset..span.end_offset])
+            })
+            .collect();
+        assert_eq!(classes, [("a&b", "a&amp;b"), ("c", "c"), ("d", "d")]);
+    }
+
+    #[test]
+    fn test_comments_dropped_unless_kept() {
+        let source = "<!-- header --><div>a<!-- inline -->b{/* note */}</div>";
+
+        let nodes = parse_document(source).unwrap();
+        assert_eq!(nodes.len(), 1);
+        let Node::Element { children, .. } = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        // The text on either side of a dropped comment is one text node
+        assert!(matches!(&children[..], [Node::Text { content, .. }] if content == "ab"));
+
+        let nodes = Parser::new(Lexer::new(source)).keep_comments(true).parse_document().unwrap();
+        assert!(matches!(&nodes[0], Node::Comment { content, .. } if content == " header "));
+        let Node::Element { children, .. } = &nodes[1] else {
+            panic!("Expected element node");
+        };
+        assert!(matches!(&children[1], Node::Comment { content, .. } if content == " inline "));
+        assert!(matches!(&children[3], Node::Comment { content, .. } if content == " note "));
+    }
+
+    fn messages(errors: &[DesignTimeError]) -> Vec<String> {
+        errors.iter()
+            .map(|e| match e {
+                DesignTimeError::ParserError { message, .. } => message.clone(),
+                other => panic!("Expected parser error, got {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recovering_reports_every_error() {
+        let source = "<div 5 id=\"a\"><p>one</span><b>two</b></div><section class=>ok</section>";
+        let (nodes, errors) = Parser::new(Lexer::new(source)).parse_document_recovering();
+
+        assert_eq!(messages(&errors), [
+            "Expected attribute name, got Unknown('5')",
+            "Unexpected closing tag </span>",
+            "Mismatched closing tag: expected </p>, got </div>",
+            "Expected attribute value, got Gt",
+        ]);
+
+        assert_eq!(nodes.len(), 2);
+        let Node::Element { attributes, children, .. } = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        assert_eq!(attributes[0].name, "id");
+        // <p> is left open until </div>, so <b> ends up inside it
+        let Node::Element { tag_name, children: p_children, .. } = &children[0] else {
+            panic!("Expected <p> element");
+        };
+        assert_eq!(tag_name, "p");
+        assert_eq!(p_children.len(), 2);
+        assert!(matches!(&nodes[1], Node::Element { tag_name, children, .. } if tag_name == "section" && children.len() == 1));
+    }
+
+    #[test]
+    fn test_recovering_closes_elements_at_ancestor_or_eof() {
+        let source = "<main><ul><li>a</ul><p>b</p></main><footer>";
+        let (nodes, errors) = Parser::new(Lexer::new(source)).parse_document_recovering();
+
+        assert_eq!(messages(&errors), [
+            "Mismatched closing tag: expected </li>, got </ul>",
+            "Unexpected end of file",
+        ]);
+        assert_eq!(nodes.len(), 2);
+        let Node::Element { children, .. } = &nodes[0] else {
+            panic!("Expected element node");
+        };
+        let tags: Vec<&str> = children.iter()
+            .filter_map(|n| match n {
+                Node::Element { tag_name, .. } => Some(tag_name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tags, ["ul", "p"]);
+    }
+
+    #[test]
+    fn test_recovering_stray_closing_tag_at_top_level() {
+        let (nodes, errors) = Parser::new(Lexer::new("</div>text<p>ok</p>")).parse_document_recovering();
+
+        assert_eq!(messages(&errors), ["Unexpected closing tag </div>"]);
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_script_and_style_content_is_raw_text() {
+        let source = "<head><style>a > b { color: red }</style><script type=module>if (a < b && c) { go('</div>') }</script></head>";
+        let mut parser = Parser::new(Lexer::new(source));
+        let node = parser.parse().unwrap();
+
+        let Node::Element { children, .. } = node else { panic!("Expected element") };
+        assert_eq!(children.len(), 2);
+        assert!(matches!(&children[0], Node::RawText { tag_name, content, .. }
+            if tag_name == "style" && content == "a > b { color: red }"));
+        let Node::RawText { tag_name, attributes, content, span } = &children[1] else { panic!("Expected raw text") };
+        assert_eq!(tag_name, "script");
+        assert_eq!(attributes[0].name, "type");
+        assert_eq!(content, "if (a < b && c) { go('</div>') }");
+        let span = span.unwrap();
+        assert_eq!(&source[span.start_offset..span.end_offset], "<script type=module>if (a < b && c) { go('</div>') }</script>");
+    }
+
+    #[test]
+    fn test_whitespace_collapses_except_in_pre() {
+        let source = "<div>\n  <b>a</b> <i>b</i>\n  <p>  one\n    two  </p>\n  <pre>\n  keep   this\n</pre>\n  <span preserve-whitespace> x  y </span>\n</div>";
+        let node = parse(source).unwrap();
+
+        let Node::Element { children, .. } = node else { panic!("Expected element") };
+        assert_eq!(children.len(), 6);
+        assert!(matches!(&children[1], Node::Text { content, .. } if content == " "));
+        let text = |node: &Node| match node {
+            Node::Element { children, .. } => match &children[..] {
+                [Node::Text { content, .. }] => content.clone(),
+                _ => panic!("Expected a single text child"),
+            },
+            _ => panic!("Expected element"),
+        };
+        assert_eq!(text(&children[3]), " one two ");
+        assert_eq!(text(&children[4]), "  keep   this\n");
+        assert_eq!(text(&children[5]), " x  y ");
+        assert_eq!(text(&parse("<pRe>\n a  b</pRe>").unwrap()), " a  b");
+    }
+
+    #[test]
+    fn test_line_breaks_before_inline_content_keep_a_space() {
+        let text = |source: &str| match parse(source).unwrap() {
+            Node::Element { children, .. } => children
+                .iter()
+                .map(|child| match child {
+                    Node::Text { content, .. } => content.clone(),
+                    Node::Expression { expression, .. } => format!("{{{}}}", expression),
+                    Node::Element { tag_name, .. } => format!("<{}>", tag_name),
+                    other => panic!("Unexpected node {:?}", other),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("Expected element"),
+        };
+
+        assert_eq!(text("<p>one\n<b>two</b></p>"), ["one ", "<b>"]);
+        assert_eq!(text("<p>Read the\n<a href=\"/d\">docs</a>\nplease</p>"), ["Read the ", "<a>", " please"]);
+        assert_eq!(text("<p>Hi\n{name}</p>"), ["Hi ", "{name}"]);
+        assert_eq!(text("<p>\n  <b>a</b>\n  <i>b</i>\n</p>"), ["<b>", "<i>"]);
+        assert_eq!(text("<p>a <!-- c --> b</p>"), ["a b"]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_expressions_and_interpolated_attributes() {
+        let node = parse(r#"<a href={url} class="p-{size}">Hi {user.name}!</a>"#).unwrap();
+
+        let Node::Element { attributes, class_names, children, .. } = node else { panic!("Expected element") };
+        assert!(matches!(&attributes[0].value, AttributeValue::Interpolated(parts)
+            if matches!(&parts[..], [AttributePart::Expression { expression, .. }] if expression == "url")));
+        assert!(matches!(&attributes[1].value, AttributeValue::Interpolated(parts) if parts.len() == 2));
+        assert!(class_names.is_empty());
+        assert_eq!(children.len(), 3);
+        assert!(matches!(&children[1], Node::Expression { expression, .. } if expression == "user.name"));
+        assert!(matches!(&children[2], Node::Text { content, .. } if content == "!"));
+
+        let err = parse("<p>{user.name</p>").unwrap_err();
+        assert!(err.to_string().contains("Unterminated expression"));
+    }
+
+    #[test]
+    fn test_component_named_like_a_raw_text_element() {
+        let node = parse("<Style theme='dark'><b>hi</b></Style>").unwrap();
+        let Node::Component { name, children, .. } = &node else { panic!("Expected a component") };
+        assert_eq!(name, "Style");
+        assert!(matches!(&children[..], [Node::Element { tag_name, .. }] if tag_name == "b"));
+    }
+
+    #[test]
+    fn test_closing_tags_of_html_elements_ignore_case() {
+        assert!(matches!(parse("<script>x</SCRIPT>").unwrap(), Node::RawText { content, .. } if content == "x"));
+        assert!(matches!(parse("<div><b>a</B></DIV>").unwrap(), Node::Element { children, .. } if children.len() == 1));
+
+        let (_, errors) = Parser::new(Lexer::new("<ul><li>a</UL>")).parse_document_recovering();
+        assert_eq!(messages(&errors), ["Mismatched closing tag: expected </li>, got </UL>"]);
+
+        let err = parse("<Card>a</card>").unwrap_err();
+        assert!(err.to_string().contains("expected </Card>, got </card>"));
+    }
+
+    #[test]
+    fn test_parse_if_blocks() {
+        let source = "<ul>\n  {#if user.admin}\n    <li>Admin</li>\n  {:else if user}<li>{user.name}</li>{:else}Guest{/if}\n</ul>";
+        let node = parse(source).unwrap();
+
+        let Node::Element { children, .. } = node else { panic!("Expected element") };
+        let [Node::If { branches, otherwise, span }] = &children[..] else { panic!("Expected a single if block") };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].condition, "user.admin");
+        let condition = branches[0].condition_span.unwrap();
+        assert_eq!(&source[condition.start_offset..condition.end_offset], "user.admin");
+        assert_eq!((condition.start_line, condition.start_column), (2, 7));
+        assert!(matches!(&branches[0].children[..], [Node::Element { tag_name, .. }] if tag_name == "li"));
+        assert_eq!(branches[1].condition, "user");
+        assert!(matches!(otherwise.as_deref(), Some([Node::Text { content, .. }]) if content == "Guest"));
+        let span = span.unwrap();
+        assert!(source[span.start_offset..span.end_offset].starts_with("{#if") && source[..span.end_offset].ends_with("{/if}"));
+
+        let (_, errors) = Parser::new(Lexer::new("{#if a}<p>x{/if}</p>{:else}{#if}{/if}")).parse_document_recovering();
+        assert_eq!(messages(&errors), [
+            "Unexpected {/if}: <p> is still open",
+            "Unexpected closing tag </p>",
+            "Unexpected {:else} outside of a block",
+            "Missing condition in {#if}",
+            "Unexpected {/if} outside of a block",
+        ]);
+    }
+
+    #[test]
+    fn test_parse_each_blocks() {
+        let source = "<ul>{#each page.users as user, i (user.id)}<li>{user.name}</li>{:else}<li>None</li>{/each}</ul>";
+        let node = parse(source).unwrap();
+
+        let Node::Element { children, .. } = node else { panic!("Expected element") };
+        let [Node::Each { items, items_span, item, index, key, key_span, children, otherwise, .. }] = &children[..] else {
+            panic!("Expected a single each block");
+        };
+        let slice = |span: &Option<Span>| {
+            let span = span.expect("span should be set");
+            &source[span.start_offset..span.end_offset]
+        };
+        assert_eq!((items.as_str(), slice(items_span)), ("page.users", "page.users"));
+        assert_eq!(item, "user");
+        assert_eq!(index.as_deref(), Some("i"));
+        assert_eq!((key.as_deref(), slice(key_span)), (Some("user.id"), "user.id"));
+        assert_eq!(children.len(), 1);
+        assert!(otherwise.is_some());
+
+        let node = parse("<p>{#each tags as tag}{tag} {/each}</p>").unwrap();
+        let Node::Element { children, .. } = node else { panic!("Expected element") };
+        assert!(matches!(&children[0], Node::Each { index: None, key: None, .. }));
+
+        let err = parse("<p>{#each tags}{/each}</p>").unwrap_err();
+        assert!(err.to_string().contains("Expected 'as' in {#each}"));
+        let err = parse("<p>{#each tags as tag}{/if}</p>").unwrap_err();
+        assert!(err.to_string().contains("Unexpected {/if} in {#each} block"));
+    }
+}
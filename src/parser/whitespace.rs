@@ -0,0 +1,128 @@
+// HTML-like whitespace collapsing for text nodes
+
+use designtime_ast::{Node, Span};
+
+/// Collapses whitespace in the text nodes of one sibling list and drops text left empty.
+///
+/// As in HTML every run of whitespace becomes a single space, so `one\n<b>two</b>` keeps its space.
+/// Text that is only whitespace with a line break is indentation between tags and is dropped, and
+/// line-breaking whitespace at the very start and end of the list is trimmed. Adjacent text nodes,
+/// such as the two sides of a dropped comment, are merged first. Text spans are narrowed to match,
+/// using `source` to work out the new positions.
+pub fn collapse_whitespace(nodes: Vec<Node>, source: &str) -> Vec<Node> {
+    let nodes = merge_text(nodes);
+    let last = nodes.len().saturating_sub(1);
+    nodes
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, node)| match node {
+            Node::Text { content, .. } if content.contains('\n') && content.chars().all(is_collapsible) => None,
+            Node::Text { content, span } => {
+                let content = collapse_text(&content, i == 0, i == last);
+                let span = span.map(|span| trim_span(span, source, i == 0, i == last));
+                (!content.is_empty()).then_some(Node::Text { content, span })
+            }
+            other => Some(other),
+        })
+        .collect()
+}
+
+fn merge_text(nodes: Vec<Node>) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match (merged.last_mut(), node) {
+            (Some(Node::Text { content, span }), Node::Text { content: next, span: next_span }) => {
+                content.push_str(&next);
+                *span = span.zip(next_span).map(|(span, next_span)| span.to(next_span));
+            }
+            (_, node) => merged.push(node),
+        }
+    }
+    merged
+}
+
+/// `trim_start` and `trim_end` drop line-breaking whitespace at that end instead of keeping a space.
+fn collapse_text(text: &str, trim_start: bool, trim_end: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let word_len = rest.find(is_collapsible).unwrap_or(rest.len());
+        out.push_str(&rest[..word_len]);
+        rest = &rest[word_len..];
+
+        let run_len = rest.len() - rest.trim_start_matches(is_collapsible).len();
+        if run_len == 0 {
+            continue;
+        }
+        let at_edge = (trim_start && out.is_empty()) || (trim_end && run_len == rest.len());
+        if !(at_edge && rest[..run_len].contains('\n')) {
+            out.push(' ');
+        }
+        rest = &rest[run_len..];
+    }
+    out
+}
+
+/// Moves the ends of a text span past the line-breaking whitespace that `collapse_text` removed.
+fn trim_span(span: Span, source: &str, trim_start: bool, trim_end: bool) -> Span {
+    let Some(raw) = source.get(span.start_offset..span.end_offset) else {
+        return span;
+    };
+    let mut span = span;
+
+    let leading = &raw[..raw.len() - raw.trim_start_matches(is_collapsible).len()];
+    if trim_start && leading.contains('\n') && leading.len() < raw.len() {
+        span.start_offset += leading.len();
+        span.start_line += leading.matches('\n').count();
+        span.start_column = leading.rsplit('\n').next().map_or(0, |tail| tail.chars().count());
+    }
+
+    let trailing = &raw[raw.trim_end_matches(is_collapsible).len()..];
+    if trim_end && trailing.contains('\n') && trailing.len() < raw.len() {
+        span.end_offset -= trailing.len();
+        span.end_line -= trailing.matches('\n').count();
+        let line = source[..span.end_offset].rsplit('\n').next().unwrap_or("");
+        span.end_column = line.chars().count();
+    }
+
+    span
+}
+
+/// Only ASCII whitespace collapses, as in HTML; `&nbsp;` is kept.
+fn is_collapsible(c: char) -> bool {
+    c.is_ascii_whitespace()
+}
+
+/// HTML ignores a single line break straight after `<pre>` or `<textarea>`.
+pub fn strip_leading_newline(children: &mut [Node]) {
+    if let Some(Node::Text { content, .. }) = children.first_mut()
+        && let Some(stripped) = content.strip_prefix("\r\n").or_else(|| content.strip_prefix('\n'))
+    {
+        *content = stripped.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse_text() {
+        assert_eq!(collapse_text("\n    Hello,\n    world  !\n", true, true), "Hello, world !");
+        assert_eq!(collapse_text("Hello\n", false, false), "Hello ");
+        assert_eq!(collapse_text("\n  Hello", false, true), " Hello");
+        assert_eq!(collapse_text(" ", true, true), " ");
+        assert_eq!(collapse_text("a\u{A0}\u{A0}b \t", true, true), "a\u{A0}\u{A0}b ");
+    }
+
+    #[test]
+    fn test_trim_span_skips_indentation() {
+        let source = "<p>\n  Hi there\n</p>";
+        let span = Span { start_line: 1, start_column: 3, start_offset: 3, end_line: 3, end_column: 0, end_offset: 15 };
+
+        let trimmed = trim_span(span, source, true, true);
+        assert_eq!(&source[trimmed.start_offset..trimmed.end_offset], "Hi there");
+        assert_eq!((trimmed.start_line, trimmed.start_column), (2, 2));
+        assert_eq!((trimmed.end_line, trimmed.end_column), (2, 10));
+    }
+}
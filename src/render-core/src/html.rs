@@ -0,0 +1,124 @@
+use designtime_ast::{is_raw_text_element, is_void_element};
+use dominate::dom::DomNode;
+
+/// Serialize a list of DOM nodes into an HTML string
+pub fn render_html(nodes: &[DomNode]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        write_node(&mut out, node);
+    }
+    out
+}
+
+fn write_node(out: &mut String, node: &DomNode) {
+    match node {
+        DomNode::Element { tag, attributes, class_names, children, inline_style, .. } => {
+            out.push('<');
+            out.push_str(tag);
+
+            if !class_names.is_empty() && !attributes.iter().any(|(name, _)| name == "class") {
+                out.push_str(&format!(" class=\"{}\"", escape_attribute(&class_names.join(" "))));
+            }
+            for (name, value) in attributes {
+                match value {
+                    Some(value) => out.push_str(&format!(" {}=\"{}\"", name, escape_attribute(value))),
+                    // Boolean attributes are written without a value
+                    None => out.push_str(&format!(" {}", name)),
+                }
+            }
+            if let Some(style) = inline_style {
+                out.push_str(&format!(" style=\"{}\"", escape_attribute(style)));
+            }
+            out.push('>');
+
+            if is_void_element(tag) {
+                return;
+            }
+
+            for child in children {
+                match child {
+                    // Script and style content is not markup, so it is written as-is
+                    DomNode::Text(text) if is_raw_text_element(tag) => out.push_str(text),
+                    _ => write_node(out, child),
+                }
+            }
+            out.push_str(&format!("</{}>", tag));
+        }
+        DomNode::Text(text) => out.push_str(&escape_text(text)),
+        DomNode::Expression(expr) => out.push_str(&escape_text(expr)),
+    }
+}
+
+/// Escape text content so it is never read back as markup.
+pub fn escape_text(text: &str) -> String {
+    escape(text, false)
+}
+
+/// Escape a value for use inside a double-quoted attribute.
+pub fn escape_attribute(value: &str) -> String {
+    escape(value, true)
+}
+
+fn escape(input: &str, in_attribute: bool) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if in_attribute => out.push_str("&quot;"),
+            '\u{A0}' => out.push_str("&nbsp;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_boolean_and_empty_attributes() {
+        let node = DomNode::element("input")
+            .attr("type", "checkbox")
+            .bool_attr("checked")
+            .attr("value", "")
+            .build();
+
+        assert_eq!(render_html(&[node]), r#"<input type="checkbox" checked value="">"#);
+    }
+
+    #[test]
+    fn test_render_nested_elements_with_classes() {
+        let node = DomNode::element("div")
+            .class("flex")
+            .class("p-4")
+            .child(DomNode::element("p").child(DomNode::text("Hello")).build())
+            .build();
+
+        assert_eq!(render_html(&[node]), r#"<div class="flex p-4"><p>Hello</p></div>"#);
+    }
+
+    #[test]
+    fn test_render_escapes_text_and_attributes() {
+        let node = DomNode::element("a")
+            .attr("title", r#"say "hi" & <go>"#)
+            .child(DomNode::text("1 < 2 && 3 > 2"))
+            .build();
+
+        assert_eq!(
+            render_html(&[node]),
+            r#"<a title="say &quot;hi&quot; &amp; &lt;go&gt;">1 &lt; 2 &amp;&amp; 3 &gt; 2</a>"#
+        );
+    }
+
+    #[test]
+    fn test_render_script_content_unescaped() {
+        let node = DomNode::element("script")
+            .child(DomNode::text("if (a < b && c) {}"))
+            .build();
+
+        assert_eq!(render_html(&[node]), "<script>if (a < b && c) {}</script>");
+    }
+}
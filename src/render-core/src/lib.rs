@@ -1,7 +1,12 @@
+pub mod html;
+
 use dominate::dom::{DomNode};
+use dominate::transform::flatten_nodes;
 use designtime_ast::Node;
 use thiserror::Error;
 
+pub use html::{escape_attribute, escape_text, render_html};
+
 #[derive(Error, Debug)]
 pub enum RenderError {
     #[error("DOM operation failed")]
@@ -13,23 +18,35 @@ pub enum RenderError {
 
 /// Core rendering function that transforms a list of AST nodes into DOM nodes
 pub fn render_nodes(nodes: Vec<Node>) -> Vec<DomNode> {
-    nodes.into_iter().map(transform_node).collect()
+    flatten_nodes(nodes).into_iter().map(transform_node).collect()
 }
 
 /// Transform a single AST node into a DOM node
 fn transform_node(node: Node) -> DomNode {
     match node {
-        Node::Element { tag_name, attributes, children, .. } => {
+        Node::Element { tag_name, attributes, children, key, .. } => {
             let mut builder = DomNode::element(&tag_name)
-                .attributes(attributes);
+                .attributes(attributes.into_iter()
+                    .map(|attr| (attr.name, attr.value.into_value()))
+                    .collect());
+            if let Some(key) = &key {
+                builder = builder.key(key);
+            }
             
-            for child in children {
+            for child in flatten_nodes(children) {
                 builder = builder.child(transform_node(child));
             }
             
             builder.build()
         }
-        Node::Text(content) => DomNode::text(&content),
+        Node::Text { content, .. } => DomNode::text(&content),
+        Node::Expression { expression, .. } => DomNode::expression(expression.trim()),
+        Node::RawText { tag_name, attributes, content, .. } => DomNode::element(&tag_name)
+            .attributes(attributes.into_iter()
+                .map(|attr| (attr.name, attr.value.into_value()))
+                .collect())
+            .child(DomNode::text(&content))
+            .build(),
         // Handle other node variants as needed
         _ => DomNode::text("Unsupported node type"),
     }
@@ -37,17 +54,17 @@ fn transform_node(node: Node) -> DomNode {
 
 /// Transform a list of AST nodes into a list of DOM nodes
 pub fn transform(nodes: Vec<Node>) -> Vec<DomNode> {
-    nodes.into_iter().map(transform_node).collect()
+    flatten_nodes(nodes).into_iter().map(transform_node).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use designtime_ast::Node;
+    use designtime_ast::{Attribute, AttributeValue, ClassName, Node};
 
     #[test]
     fn test_transform_text_node() {
-        let node = Node::Text("Hello".to_string());
+        let node = Node::text("Hello");
         let dom_node = transform_node(node);
         
         if let DomNode::Text(text) = dom_node {
@@ -61,9 +78,11 @@ mod tests {
     fn test_transform_element_node() {
         let node = Node::Element {
             tag_name: "div".to_string(),
-            attributes: vec![("class".to_string(), "container".to_string())],
-            children: vec![Node::Text("Hello".to_string())],
-            class_names: vec!["container".to_string()],
+            attributes: vec![Attribute::new("class", AttributeValue::Literal("container".to_string()))],
+            children: vec![Node::text("Hello")],
+            class_names: vec![ClassName::new("container")],
+            key: None,
+            span: None,
         };
         
         let dom_node = transform_node(node);
@@ -72,7 +91,7 @@ mod tests {
             
         let class_attr = attributes.iter()
         .find(|(k, _)| k == "class")
-        .map(|(_, v)| v);
+        .and_then(|(_, v)| v.as_ref());
     
             assert_eq!(tag, "div");
             assert_eq!(class_attr, Some(&"container".to_string()));
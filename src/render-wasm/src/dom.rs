@@ -19,7 +19,8 @@ pub fn render_node(
             })?;
             
             for (k, v) in attributes {
-                el.set_attribute(k, v)
+                // Boolean attributes are present with an empty value
+                el.set_attribute(k, v.as_deref().unwrap_or(""))
                     .map_err(|e| JsValue::from_str(&format!("Failed to set attribute: {:?}", e)))?;
             }
 
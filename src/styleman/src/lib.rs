@@ -1,3 +1,5 @@
+pub mod scoped;
 pub mod styleman;
 
-pub use styleman::StyleMan;
\ No newline at end of file
+pub use scoped::{scope_attribute, scope_css};
+pub use styleman::StyleMan;
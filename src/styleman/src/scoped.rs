@@ -0,0 +1,160 @@
+// Scoped CSS: rules rewritten to only match the elements of one component
+
+/// The attribute marking a component's elements, derived from `id` (e.g. its file path),
+/// such as `data-dt-1a2b3c4d`. The same `id` always gives the same attribute.
+pub fn scope_attribute(id: &str) -> String {
+    // FNV-1a, so the attribute is stable across builds and platforms
+    let hash = id.bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    format!("data-dt-{:08x}", hash)
+}
+
+/// Rewrites `css` so its rules only match elements carrying `attribute`: every selector gets
+/// `[attribute]` on its last part, before any pseudo-element, so `.card h2::before` becomes
+/// `.card h2[data-dt-1a2b3c4d]::before`. Rules inside `@media`, `@supports` and the like are
+/// scoped too, while `@keyframes` and `@font-face` are left as written. Comments are dropped.
+pub fn scope_css(css: &str, attribute: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    scope_rules(&strip_comments(css), attribute, &mut out);
+    out
+}
+
+fn scope_rules(css: &str, attribute: &str, out: &mut String) {
+    let mut rest = css;
+    while let Some(open) = find_unquoted(rest, |c| c == '{' || c == ';') {
+        // At-rules without a block, like `@import url(a.css);`
+        if rest[open..].starts_with(';') {
+            out.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        }
+
+        let prelude = &rest[..open];
+        let close = matching_brace(rest, open);
+        let body = &rest[open + 1..close];
+        match prelude.trim().strip_prefix('@') {
+            Some(at_rule) => {
+                out.push_str(prelude);
+                out.push('{');
+                let name = at_rule.split(|c: char| !c.is_alphanumeric() && c != '-').next().unwrap_or_default();
+                if matches!(name, "media" | "supports" | "container" | "layer" | "document") {
+                    scope_rules(body, attribute, out);
+                } else {
+                    out.push_str(body);
+                }
+            }
+            None => {
+                let leading = &prelude[..prelude.len() - prelude.trim_start().len()];
+                out.push_str(leading);
+                let selectors: Vec<String> = split_selectors(prelude)
+                    .into_iter()
+                    .map(|selector| scope_selector(selector.trim(), attribute))
+                    .collect();
+                out.push_str(&selectors.join(", "));
+                out.push_str(" {");
+                out.push_str(body);
+            }
+        }
+        out.push('}');
+        rest = rest.get(close + 1..).unwrap_or_default();
+    }
+    out.push_str(rest);
+}
+
+/// Adds `[attribute]` to the last compound selector, before a pseudo-element if there is one.
+fn scope_selector(selector: &str, attribute: &str) -> String {
+    let mut depth = 0;
+    let mut pseudo_element = None;
+    let mut chars = selector.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            // A combinator starts a new compound selector
+            c if depth == 0 && (c.is_whitespace() || matches!(c, '>' | '+' | '~')) => pseudo_element = None,
+            ':' if depth == 0 && chars.peek().is_some_and(|&(_, next)| next == ':') => {
+                pseudo_element.get_or_insert(i);
+                chars.next();
+            }
+            ':' if depth == 0 && pseudo_element.is_none() => {
+                // CSS2 pseudo-elements written with one colon
+                let name = selector[i + 1..].split(|c: char| !c.is_alphanumeric() && c != '-').next().unwrap_or_default();
+                if matches!(name, "before" | "after" | "first-line" | "first-letter") {
+                    pseudo_element = Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let insert_at = pseudo_element.unwrap_or(selector.len());
+    format!("{}[{}]{}", &selector[..insert_at], attribute, &selector[insert_at..])
+}
+
+/// Splits a selector list at its top-level commas.
+fn split_selectors(prelude: &str) -> Vec<&str> {
+    let mut selectors = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut quote = None;
+    for (i, c) in prelude.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[') => depth += 1,
+            (None, ')' | ']') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                selectors.push(&prelude[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    selectors.push(&prelude[start..]);
+    selectors.into_iter().filter(|selector| !selector.trim().is_empty()).collect()
+}
+
+/// Byte index of the first character matching `at` that isn't inside a string.
+fn find_unquoted(css: &str, at: impl Fn(char) -> bool) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in css.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if at(c) => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Byte index of the `}` closing the `{` at `open`, or the end of `css` if it is never closed.
+fn matching_brace(css: &str, open: usize) -> usize {
+    let mut depth = 0;
+    let mut offset = open;
+    while let Some(i) = find_unquoted(&css[offset..], |c| c == '{' || c == '}') {
+        let i = offset + i;
+        if css[i..].starts_with('{') {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                return i;
+            }
+        }
+        offset = i + 1;
+    }
+    css.len()
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = rest[start + 2..].find("*/").map_or("", |end| &rest[start + 2 + end + 2..]);
+    }
+    out.push_str(rest);
+    out
+}
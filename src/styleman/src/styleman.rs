@@ -1,13 +1,26 @@
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
+#[derive(Debug, Clone)]
 pub struct StyleMan {
-    class_names: HashSet<String>,
+    // Ordered, so generated CSS is the same from one build to the next
+    class_names: BTreeSet<String>,
+    stylesheets: Vec<String>,
 }
 
 impl StyleMan {
     pub fn new() -> Self {
         Self {
-            class_names: HashSet::new(),
+            class_names: BTreeSet::new(),
+            stylesheets: Vec::new(),
+        }
+    }
+
+    /// Add hand-written CSS, e.g. from a `<style>` block; it is emitted after the utility classes.
+    /// A stylesheet already added is skipped, so a component used many times adds its styles once.
+    pub fn add_stylesheet(&mut self, css: &str) {
+        let css = css.trim();
+        if !css.is_empty() && !self.stylesheets.iter().any(|existing| existing == css) {
+            self.stylesheets.push(css.to_string());
         }
     }
 
@@ -17,6 +30,14 @@ impl StyleMan {
         }
     }
 
+    /// Add everything `other` has, e.g. to build one stylesheet for several pages.
+    pub fn merge(&mut self, other: &StyleMan) {
+        self.add_classes(other.class_names.iter().cloned());
+        for stylesheet in &other.stylesheets {
+            self.add_stylesheet(stylesheet);
+        }
+    }
+
     pub fn generate_css(&self) -> String {
         let mut css = String::new();
         for class_name in &self.class_names {
@@ -24,6 +45,10 @@ impl StyleMan {
                 css.push_str(&format!(".{} {{ {} }}\n", class_name, rule));
             }
         }
+        for stylesheet in &self.stylesheets {
+            css.push_str(stylesheet);
+            css.push('\n');
+        }
         css
     }
 
@@ -1,22 +1,27 @@
-use std::{path::PathBuf, time::Duration};
+use std::{path::{Path, PathBuf}, time::Duration};
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
 use tokio::sync::{broadcast, mpsc};
 use warp::Filter;
 use serde_json::json;
-use wasm_bindgen::JsValue;
-use render_core::render_nodes;
+
+/// Checks a changed file given its path and contents, returning every problem found in it.
+pub type Check = Box<dyn Fn(&Path, &str) -> Result<(), Vec<String>> + Send + Sync>;
 
 pub struct Watchman {
+    check: Check,
     reload_tx: broadcast::Sender<()>,
     error_tx: mpsc::UnboundedSender<String>,
     error_rx: mpsc::UnboundedReceiver<String>,
 }
 
 impl Watchman {
-    pub fn new() -> Self {
+    /// `check` is run on every changed `.dts` file; the browser reloads once a file passes it,
+    /// and each problem is reported otherwise.
+    pub fn new(check: impl Fn(&Path, &str) -> Result<(), Vec<String>> + Send + Sync + 'static) -> Self {
         let (reload_tx, _) = broadcast::channel(16);
         let (error_tx, error_rx) = mpsc::unbounded_channel();
         Self {
+            check: Box::new(check),
             reload_tx,
             error_tx,
             error_rx,
@@ -24,7 +29,6 @@ impl Watchman {
     }
 
     pub async fn run(self) -> anyhow::Result<()> {
-        let reload_tx_watcher = self.reload_tx.clone();
         let error_tx_watcher = self.error_tx.clone();
 
         // Channel to send file changes from watcher thread to async task
@@ -44,21 +48,18 @@ impl Watchman {
                     }
                 }
 
-                println!("Watching for .dts changes... Server at http://localhost:3000");
+                eprintln!("Watching for .dts changes... Server at http://localhost:3000");
 
                 for res in rx {
                     match res {
                         Ok(event) => {
-                            if matches!(event.kind, notify::EventKind::Modify(_)) {
-                                if let Some(path) = event.paths.get(0) {
-                                    if path.extension().map_or(false, |ext| ext == "dts") {
-                                        if file_change_tx.send(path.clone()).is_err() {
-                                            eprintln!("File change receiver dropped, exiting watcher.");
-                                            break;
-                                        }
-                                        let _ = reload_tx_watcher.send(());
-                                    }
-                                }
+                            if matches!(event.kind, notify::EventKind::Modify(_))
+                                && let Some(path) = event.paths.first()
+                                && path.extension().is_some_and(|ext| ext == "dts")
+                                && file_change_tx.send(path.clone()).is_err()
+                            {
+                                eprintln!("File change receiver dropped, exiting watcher.");
+                                break;
                             }
                         }
                         Err(e) => {
@@ -67,45 +68,41 @@ impl Watchman {
                     }
                 }
 
-                println!("Watcher thread exiting.");
+                eprintln!("Watcher thread exiting.");
             })?;
 
         let error_tx_clone = self.error_tx.clone();
+        let reload_tx_processor = self.reload_tx.clone();
+        let check = self.check;
         tokio::spawn(async move {
             while let Some(path) = file_change_rx.recv().await {
-                println!("Changed file: {}", path.display());
+                eprintln!("Changed file: {}", path.display());
                 match std::fs::read_to_string(&path) {
-                    Ok(source) => {
-                        match wasm_bindgen::JsValue::from_str(&source) {
-                            Ok(json) => {
-                                match render_nodes(&json) {
-                                    Ok(_) => {
-                                        let _ = error_tx_clone.send("reload");
-                                    }
-                                    Err(e) => {
-                                        let _ = error_tx_clone.send(format!("Failed to render {}: {}", path.display(), e));
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                let _ = error_tx_clone.send(format!("Failed to read {}: {}", path.display(), e));
+                    // Every problem in the file is reported at once; only a file without any reloads the page
+                    Ok(source) => match check(&path, &source) {
+                        Ok(()) => {
+                            let _ = reload_tx_processor.send(());
+                        }
+                        Err(errors) => {
+                            for err in errors {
+                                let _ = error_tx_clone.send(err);
                             }
                         }
+                    },
+                    Err(e) => {
+                        let _ = error_tx_clone.send(format!("Failed to read {}: {}", path.display(), e));
                     }
-                    Err(_) => todo!(),
                 }
             }
-            println!("File processor task ended.");
+            eprintln!("File processor task ended.");
         });
 
         let mut error_rx = self.error_rx;
-        let error_sender = self.error_tx.clone();
         tokio::spawn(async move {
             while let Some(err) = error_rx.recv().await {
                 eprintln!("[Watchman Error] {}", err);
-                let _ = error_sender.send(err);
             }
-            println!("Error handler task ended.");
+            eprintln!("Error handler task ended.");
         });
 
         // Helper to list .dts files
@@ -115,13 +112,13 @@ impl Watchman {
                 if let Ok(entries) = std::fs::read_dir(dir) {
                     for entry in entries.flatten() {
                         let path = entry.path();
-                        if path.extension().map_or(false, |ext| ext == "dts") {
-                            if let Some(name) = path.file_name() {
-                                files.push(json!({
-                                    "name": name.to_string_lossy(),
-                                    "path": path.to_string_lossy()
-                                }));
-                            }
+                        if path.extension().is_some_and(|ext| ext == "dts")
+                            && let Some(name) = path.file_name()
+                        {
+                            files.push(json!({
+                                "name": name.to_string_lossy(),
+                                "path": path.to_string_lossy()
+                            }));
                         }
                     }
                 }
@@ -240,7 +237,7 @@ loadFiles();
 
         let routes = index_route.or(reload_route).or(files_route).or(file_route);
 
-        println!("Watchman server running at http://localhost:3000");
+        eprintln!("Watchman server running at http://localhost:3000");
         warp::serve(routes).run(([0, 0, 0, 0], 3000)).await;
 
         Ok(())